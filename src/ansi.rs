@@ -0,0 +1,316 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+use crate::highlight::TAB;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Turns text coloured with ANSI escape sequences, like the output of `bat --color=always`, into
+/// styled lines. SGR sequences (`ESC [ ... m`) set the style, other escape sequences are dropped.
+pub fn parse(text: &str) -> Vec<Line<'static>> {
+    let mut style = Style::new();
+
+    // Styles carry over from one line to the next, like they would in a terminal
+    text.lines()
+        .map(|line| parse_line(line, &mut style))
+        .collect()
+}
+
+fn parse_line(line: &str, style: &mut Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut content = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ESC => {
+                let before = *style;
+                match chars.next() {
+                    // Control Sequence Introducer, ended by a byte in `@..=~`
+                    Some('[') => {
+                        let mut params = String::new();
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                if c == 'm' {
+                                    apply_sgr(&params, style);
+                                }
+                                break;
+                            }
+                            params.push(c);
+                        }
+                    }
+                    // Operating System Command, like setting the window title or a hyperlink,
+                    // ended by BEL or `ESC \`
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == BEL || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+
+                if *style != before && !content.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut content), before));
+                }
+            }
+            '\t' => content.push_str(TAB),
+            // Other control characters would move the cursor around the pane
+            c if c.is_control() => (),
+            c => content.push(c),
+        }
+    }
+
+    if !content.is_empty() {
+        spans.push(Span::styled(content, *style));
+    }
+
+    Line::from(spans)
+}
+
+// Applies a Select Graphic Rendition sequence like `1;38;5;208`
+fn apply_sgr(params: &str, style: &mut Style) {
+    // Params are separated by `;`, and some programs give a colour's arguments as sub-params
+    // separated by `:` instead, like `38:2::255:0:0`. Empty params stand for 0, so `ESC [ m` and
+    // `ESC [ ; 1 m` start with a reset.
+    let mut params = params.split(';');
+
+    while let Some(param) = params.next() {
+        let mut parts = param.split(':');
+        let code = match parts.next().unwrap_or_default() {
+            "" => 0,
+            // Numbers too big to be codes are ignored, rather than read as a reset
+            c => match c.parse::<u8>() {
+                Ok(code) => code,
+                Err(_) => continue,
+            },
+        };
+        let subs = parts
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<u16>().ok())
+            .collect::<Option<Vec<_>>>();
+
+        *style = match code {
+            0 => Style::new(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            // `4:0` turns underlining off, other sub-params are kinds of underline
+            4 if subs == Some(vec![0]) => style.remove_modifier(Modifier::UNDERLINED),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 | 6 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            21 | 22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            28 => style.remove_modifier(Modifier::HIDDEN),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(Color::Indexed(code - 30)),
+            38 => match extended_color(subs, &mut params) {
+                Some(c) => style.fg(c),
+                None => *style,
+            },
+            39 => Style { fg: None, ..*style },
+            40..=47 => style.bg(Color::Indexed(code - 40)),
+            48 => match extended_color(subs, &mut params) {
+                Some(c) => style.bg(c),
+                None => *style,
+            },
+            49 => Style { bg: None, ..*style },
+            58 => match extended_color(subs, &mut params) {
+                Some(c) => style.underline_color(c),
+                None => *style,
+            },
+            59 => Style {
+                underline_color: None,
+                ..*style
+            },
+            90..=97 => style.fg(Color::Indexed(code - 90 + 8)),
+            100..=107 => style.bg(Color::Indexed(code - 100 + 8)),
+            _ => *style,
+        };
+    }
+}
+
+// Reads the arguments of a `38`, `48` or `58` code, either `5;n` for the 256 colour palette or
+// `2;r;g;b`. They're taken from the code's sub-params if it has any (where `2` may be followed by
+// a colour space before `r`), and otherwise from the params after it.
+fn extended_color<'a>(
+    subs: Option<Vec<u16>>,
+    params: &mut impl Iterator<Item = &'a str>,
+) -> Option<Color> {
+    let args = match subs {
+        Some(subs) if !subs.is_empty() => subs,
+        Some(_) => {
+            let kind = params.next()?.parse::<u16>().ok()?;
+            let len = match kind {
+                5 => 1,
+                2 => 3,
+                _ => return None,
+            };
+
+            // Taken even if they're invalid, so they aren't mistaken for codes
+            let values = params
+                .take(len)
+                .map(|p| p.parse::<u16>().ok())
+                .collect::<Vec<_>>();
+            std::iter::once(Some(kind))
+                .chain(values)
+                .collect::<Option<Vec<_>>>()?
+        }
+        None => return None,
+    };
+
+    let byte = |v: &u16| u8::try_from(*v).ok();
+    match args.as_slice() {
+        [5, n] => Some(Color::Indexed(byte(n)?)),
+        [2, r, g, b] | [2, _, r, g, b] => Some(Color::Rgb(byte(r)?, byte(g)?, byte(b)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The spans of a single line, as (text, style)
+    fn spans(text: &str) -> Vec<(String, Style)> {
+        let lines = parse(text);
+        assert_eq!(lines.len(), 1);
+
+        lines[0]
+            .spans
+            .iter()
+            .map(|s| (s.content.to_string(), s.style))
+            .collect()
+    }
+
+    fn span(text: &str, style: Style) -> (String, Style) {
+        (text.to_string(), style)
+    }
+
+    #[test]
+    fn reset() {
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        for reset in ["\x1b[0m", "\x1b[m"] {
+            assert_eq!(
+                spans(&format!("\x1b[1ma{}b", reset)),
+                [span("a", bold), span("b", Style::new())]
+            );
+        }
+        // An empty param is a 0 too
+        assert_eq!(
+            spans("\x1b[31ma\x1b[;1mb"),
+            [
+                span("a", Style::new().fg(Color::Indexed(1))),
+                span("b", bold)
+            ]
+        );
+    }
+
+    #[test]
+    fn basic_colors() {
+        assert_eq!(
+            spans("\x1b[31;42ma\x1b[39mb\x1b[97;49mc"),
+            [
+                span(
+                    "a",
+                    Style::new().fg(Color::Indexed(1)).bg(Color::Indexed(2))
+                ),
+                span("b", Style::new().bg(Color::Indexed(2))),
+                span("c", Style::new().fg(Color::Indexed(15))),
+            ]
+        );
+    }
+
+    #[test]
+    fn palette_colors() {
+        let orange = Style::new().fg(Color::Indexed(208));
+        assert_eq!(spans("\x1b[38;5;208ma"), [span("a", orange)]);
+        assert_eq!(spans("\x1b[38:5:208ma"), [span("a", orange)]);
+        assert_eq!(
+            spans("\x1b[1;48;5;17ma"),
+            [span(
+                "a",
+                Style::new()
+                    .add_modifier(Modifier::BOLD)
+                    .bg(Color::Indexed(17))
+            )]
+        );
+    }
+
+    #[test]
+    fn true_colors() {
+        let red = Style::new().fg(Color::Rgb(255, 10, 0));
+        for sgr in [
+            "38;2;255;10;0",
+            "38:2:255:10:0",
+            "38:2::255:10:0",
+            "38:2:1:255:10:0",
+        ] {
+            assert_eq!(
+                spans(&format!("\x1b[{}ma", sgr)),
+                [span("a", red)],
+                "{}",
+                sgr
+            );
+        }
+        // Out of range values are ignored, instead of being read as codes
+        assert_eq!(spans("\x1b[38;2;256;10;0ma"), [span("a", Style::new())]);
+    }
+
+    #[test]
+    fn extended_arguments_are_not_codes() {
+        // Without consuming its arguments, `5` would blink and `2` would dim
+        let underline = Style::new().underline_color(Color::Indexed(5));
+        assert_eq!(spans("\x1b[58;5;5ma"), [span("a", underline)]);
+        assert_eq!(
+            spans("\x1b[58;2;1;2;3ma"),
+            [span("a", Style::new().underline_color(Color::Rgb(1, 2, 3)))]
+        );
+        assert_eq!(spans("\x1b[58;5;5;59ma"), [span("a", Style::new())]);
+        // A code too big to exist isn't a reset
+        assert_eq!(
+            spans("\x1b[31;300ma"),
+            [span("a", Style::new().fg(Color::Indexed(1)))]
+        );
+    }
+
+    #[test]
+    fn tabs_are_expanded() {
+        assert_eq!(spans("a\tb"), [span(&format!("a{}b", TAB), Style::new())]);
+    }
+
+    #[test]
+    fn styles_carry_over_lines() {
+        let lines = parse("\x1b[32ma\nb\x1b[0m\nc");
+        let green = Style::new().fg(Color::Indexed(2));
+
+        assert_eq!(lines[0].spans[0].style, green);
+        assert_eq!(lines[1].spans[0].style, green);
+        assert_eq!(lines[2].spans[0].style, Style::new());
+    }
+
+    #[test]
+    fn other_sequences_are_dropped() {
+        assert_eq!(
+            spans("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07\x1b[2Kx"),
+            [span("linkx", Style::new())]
+        );
+    }
+
+    #[test]
+    fn unterminated_escapes() {
+        for text in ["a\x1b", "a\x1b[", "a\x1b[38;5", "a\x1b]0;title"] {
+            assert_eq!(spans(text), [span("a", Style::new())], "{:?}", text);
+        }
+    }
+}
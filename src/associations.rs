@@ -0,0 +1,181 @@
+use std::{fs, io::Read, path::Path};
+
+use globset::{Glob, GlobMatcher};
+use toml::{Table, Value};
+
+// Top-level MIME types, used to tell `image/*` apart from a path glob
+const MIME_TOP_LEVEL: [&str; 11] = [
+    "application",
+    "audio",
+    "font",
+    "image",
+    "inode",
+    "message",
+    "model",
+    "multipart",
+    "text",
+    "video",
+    "x-content",
+];
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Glob(GlobMatcher, bool), // true if the glob should be matched against the full path
+    Extension(String),
+    Mime(String),
+    MimeWildcard(String), // only the top-level type, e.g. `image` for `image/*`
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Self {
+        if let Some((top, sub)) = s.split_once('/') {
+            if MIME_TOP_LEVEL.contains(&top) && !sub.contains('/') {
+                return if sub == "*" {
+                    Pattern::MimeWildcard(top.to_string())
+                } else {
+                    Pattern::Mime(s.to_string())
+                };
+            }
+        }
+
+        if s.contains(['*', '?', '[', '{', '/']) {
+            let glob = Glob::new(s)
+                .unwrap_or_else(|_| panic!("Invalid glob pattern {}", s))
+                .compile_matcher();
+            Pattern::Glob(glob, s.contains('/'))
+        } else {
+            Pattern::Extension(s.trim_start_matches('.').to_lowercase())
+        }
+    }
+
+    // Lower ranks are more specific and win when several patterns match
+    fn rank(&self) -> usize {
+        match self {
+            Pattern::Glob(..) => 0,
+            Pattern::Extension(_) => 1,
+            Pattern::Mime(_) => 2,
+            Pattern::MimeWildcard(_) => 3,
+        }
+    }
+
+    fn matches(&self, path: &Path, mime: &mut Option<String>) -> bool {
+        match self {
+            Pattern::Glob(g, full) => {
+                if *full {
+                    g.is_match(path)
+                } else {
+                    path.file_name().is_some_and(|n| g.is_match(n))
+                }
+            }
+            Pattern::Extension(e) => path
+                .extension()
+                .is_some_and(|x| x.to_string_lossy().to_lowercase() == *e),
+            Pattern::Mime(m) => mime.get_or_insert_with(|| mime_type(path)) == m,
+            Pattern::MimeWildcard(t) => mime
+                .get_or_insert_with(|| mime_type(path))
+                .split('/')
+                .next()
+                .is_some_and(|top| top == t),
+        }
+    }
+}
+
+/// Maps glob patterns, extensions and MIME types to values. Entries from the user config are
+/// always preferred over the defaults, and within each of those globs beat extensions, which
+/// beat exact MIME types, which beat MIME wildcards.
+#[derive(Clone, Debug)]
+pub struct Associations<T> {
+    entries: Vec<(usize, Pattern, T)>, // (tier, pattern, value), sorted by precedence
+}
+
+impl<T> Associations<T> {
+    pub fn from_tables(
+        section: &str,
+        user_table: &Table,
+        default_table: &Table,
+        parse: impl Fn(&Value) -> Option<T>,
+    ) -> Self {
+        let empty = Table::new();
+        let user = match user_table.get(section) {
+            Some(Value::Table(t)) => t,
+            _ => &empty,
+        };
+        let default = match default_table.get(section) {
+            Some(Value::Table(t)) => t,
+            _ => &empty,
+        };
+
+        let mut entries = Vec::new();
+        for (tier, table) in [user, default].into_iter().enumerate() {
+            for (k, v) in table {
+                if tier > 0 && user.contains_key(k) {
+                    continue;
+                }
+
+                let value = parse(v)
+                    .unwrap_or_else(|| panic!("Invalid value for {} in [{}] section", k, section));
+                entries.push((tier, Pattern::parse(k), value));
+            }
+        }
+
+        entries.sort_by_key(|(tier, p, _)| (*tier, p.rank()));
+
+        Self { entries }
+    }
+
+    /// Returns the most specific value associated with `path`
+    pub fn lookup(&self, path: &Path) -> Option<&T> {
+        let mut mime = None;
+        self.entries
+            .iter()
+            .find(|(_, p, _)| p.matches(path, &mut mime))
+            .map(|(_, _, v)| v)
+    }
+
+    /// Returns every value associated with `path`, most specific first
+    pub fn lookup_all(&self, path: &Path) -> Vec<&T> {
+        let mut mime = None;
+        self.entries
+            .iter()
+            .filter(|(_, p, _)| p.matches(path, &mut mime))
+            .map(|(_, _, v)| v)
+            .collect()
+    }
+}
+
+/// Detects the MIME type of a file, first from its contents and then from its extension
+pub fn mime_type(path: &Path) -> String {
+    if path.is_dir() {
+        return "inode/directory".to_string();
+    }
+
+    if let Ok(Some(t)) = infer::get_from_path(path) {
+        return t.mime_type().to_string();
+    }
+
+    if let Some(m) = mime_guess::from_path(path).first() {
+        return m.essence_str().to_string();
+    }
+
+    if is_text_file(path) {
+        "text/plain".to_string()
+    } else {
+        "application/octet-stream".to_string()
+    }
+}
+
+/// Guesses whether a file is text from its first KiB
+pub fn is_text_file(path: &Path) -> bool {
+    let mut buf = Vec::new();
+    fs::File::open(path)
+        .and_then(|f| f.take(1024).read_to_end(&mut buf))
+        .is_ok_and(|_| is_text(&buf))
+}
+
+/// Guesses whether the start of a file is text, which it isn't if it has NUL bytes or invalid
+/// UTF-8
+pub fn is_text(start: &[u8]) -> bool {
+    // A multi-byte character cut off at the end of the buffer is still text
+    !start.contains(&0)
+        && std::str::from_utf8(start).map_or_else(|e| e.error_len().is_none(), |_| true)
+}
@@ -1,19 +1,38 @@
 use std::{
-    env::{current_dir, set_current_dir},
+    collections::HashMap,
+    env::set_current_dir,
     fs::{self},
-    io::Write,
+    io::{self, ErrorKind},
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    thread,
     time::Duration,
 };
 
 use crate::{
-    components::{ClipboardEntry, File, Window, BLOCK_LINES, CURR_DIR_LINES, TOTAL_USED_LINES},
-    config::{Config, FileListCommand, FinderCommand, OmnibarCommand, OmnibarType},
-    tui::Tui,
+    components::{
+        format_size, format_time, ClipboardEntry, File, Window, BLOCK_LINES, CURR_DIR_LINES,
+        DIR_COLOR, FILE_COLOR, TOTAL_USED_LINES,
+    },
+    config::{
+        Config, ConfirmCommand, ConflictCommand, FileListCommand, FinderCommand, FinderType,
+        JumpBackend, OmnibarCommand, OmnibarType, TrashCommand,
+    },
+    frecency::Frecency,
+    fsops,
+    fuzzy::Match,
+    highlight::Highlighter,
+    journal::{Journal, Operation},
+    opener::Opener,
+    preview::Pane,
+    rename::{RenamePattern, RenamePlan},
+    search::{Filters, Grep, GrepHit, Search, Walker},
+    trash::{Trash, TrashEntry},
+    tui::{self, Tui},
+    vfs::{self, Format, Progress},
     Result,
 };
-use ignore::Walk;
+use globset::Glob;
 use ratatui::crossterm::{
         event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
         style::Color,
@@ -23,45 +42,58 @@ pub struct Browser {
     window: Window,
     terminal: Tui,
     config: Config,
+    trash: Trash,
+    journal: Journal,
+    frecency: Frecency,
+    highlighter: Highlighter,
     curr_dir: PathBuf,
     exit: bool,
 }
 
+// Where the finder's results come from
+enum FinderSource {
+    Files(Search),
+    Jump,
+    Grep(Grep, Vec<GrepHit>), // along with the hits currently shown
+}
+
+// Lists the entries of `dir`, which may be an archive or a directory inside one
 fn fetch_files(dir: &Path) -> Result<Vec<File>> {
-    let paths = std::fs::read_dir(dir)?
-        .map(|d| d.expect("Unable to fetch files in directory").path())
-        .collect::<Vec<_>>();
+    let (dirs, files) = match vfs::split(dir) {
+        Some((archive, inner)) => vfs::read_dir(&archive, &inner)?,
+        None => {
+            let paths = std::fs::read_dir(dir)?
+                .map(|d| d.expect("Unable to fetch files in directory").path())
+                .collect::<Vec<_>>();
 
-    let (mut dirs, mut files): (Vec<_>, Vec<_>) = paths.into_iter().partition(|p| p.is_dir());
-    dirs.sort();
-    files.sort();
+            let (mut dirs, mut files): (Vec<_>, Vec<_>) =
+                paths.into_iter().partition(|p| p.is_dir());
+            dirs.sort();
+            files.sort();
+
+            let names = |paths: Vec<PathBuf>| {
+                paths
+                    .into_iter()
+                    .map(|p| {
+                        p.strip_prefix(dir)
+                            .expect("Unable to parse entry")
+                            .to_string_lossy()
+                            .to_string()
+                    })
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+            };
+
+            (names(dirs), names(files))
+        }
+    };
 
     let mut entries: Vec<_> = dirs
         .into_iter()
-        .map(|p| {
-            p.strip_prefix(dir)
-                .expect("Unable to parse dir")
-                .to_string_lossy()
-                .to_string()
-        })
-        .filter(|s| !s.is_empty())
-        .map(|d| File::new(d, Color::DarkYellow))
+        .map(|d| File::new(d, DIR_COLOR))
+        .chain(files.into_iter().map(|f| File::new(f, FILE_COLOR)))
         .collect();
 
-    entries.append(
-        &mut files
-            .into_iter()
-            .map(|p| {
-                p.strip_prefix(dir)
-                    .expect("Unable to parse file")
-                    .to_string_lossy()
-                    .to_string()
-            })
-            .filter(|s| !s.is_empty())
-            .map(|f| File::new(f, Color::Cyan))
-            .collect(),
-    );
-
     entries.insert(0, File::new(".".to_string(), Color::White));
     entries.insert(0, File::new("..".to_string(), Color::White));
 
@@ -73,10 +105,17 @@ impl Browser {
         let curr_dir = std::env::current_dir()?;
         let files = fetch_files(curr_dir.as_path())?;
 
+        let mut frecency = Frecency::init(config.import_zoxide())?;
+        frecency.visit(&curr_dir)?;
+
         Ok(Self {
             window: Window::new(files, curr_dir.to_string_lossy().to_string()),
             terminal,
             config,
+            trash: Trash::init()?,
+            journal: Journal::init()?,
+            frecency,
+            highlighter: Highlighter::new(),
             curr_dir,
             exit: false,
         })
@@ -94,9 +133,15 @@ impl Browser {
             self.window
                 .preview
                 .set_max_lines((f.area().height - f.area().height / 3 - BLOCK_LINES) as usize);
+            self.window
+                .preview
+                .set_width((f.area().width - 3 * f.area().width / 4 - BLOCK_LINES) as usize);
             self.window
                 .clipboard
                 .set_max_entries((f.area().height / 3 - BLOCK_LINES) as usize);
+            self.window
+                .trash
+                .set_max_entries((f.area().height - BLOCK_LINES) as usize);
         })?;
 
         Ok(())
@@ -144,7 +189,14 @@ impl Browser {
             if event::poll(Duration::from_millis(16))? {
                 if let event::Event::Key(ke) = event::read()? {
                     let command = self.file_list_command(ke);
-                    self.execute_file_list_command(command)?
+                    if !matches!(command, FileListCommand::None) {
+                        self.window.status.clear();
+                    }
+
+                    // Failed file operations shouldn't take the whole browser down with them
+                    if let Err(e) = self.execute_file_list_command(command) {
+                        self.window.status.error(e.to_string());
+                    }
                 }
             }
 
@@ -198,34 +250,190 @@ impl Browser {
         Ok(())
     }
 
-    fn finder_mode(&mut self, zoxide: bool) -> Result<()> {
-        self.window.finder_mode(true);
-        self.window
-            .finder
-            .update_files(self.find(String::new(), zoxide)?);
+    // Extends a selection from the current entry as the cursor moves, marking it once confirmed
+    fn visual_mode(&mut self) -> Result<()> {
+        self.window.file_list.start_visual();
 
-        loop {
+        let confirm = loop {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    match self.file_list_command(ke) {
+                        FileListCommand::EntryScroll(d) => self.window.file_list.scroll_entry(d),
+                        FileListCommand::VisualMode | FileListCommand::SelectEntry => break true,
+                        FileListCommand::ExitHint => break false,
+                        _ => (),
+                    }
+                }
+            }
+
+            self.draw()?;
+        };
+
+        self.window.file_list.end_visual(confirm);
+
+        Ok(())
+    }
+
+    fn finder_mode(&mut self, kind: FinderType) -> Result<()> {
+        self.window.finder_mode(true, kind);
+        self.window.preview.reset_page();
+
+        // Recent directories can be looked up on every keystroke, but walking a big directory tree
+        // can't, so that happens in the background while the results are filtered
+        let mut filters = self.config.finder_filters().clone();
+        let mut source = match kind {
+            FinderType::Files => FinderSource::Files(self.start_search(&filters)?),
+            FinderType::Jump => {
+                self.window.finder.update_files(self.jump(String::new())?);
+                FinderSource::Jump
+            }
+            FinderType::Grep => FinderSource::Grep(self.start_grep(&filters), Vec::new()),
+        };
+        let mut previewed = None;
+
+        let result = loop {
             if event::poll(Duration::from_millis(16))? {
                 if let Event::Key(ke) = event::read()? {
                     let command = self.finder_command(ke);
-                    if self.execute_finder_command(command, zoxide)? {
-                        break;
+
+                    // Filters don't apply when jumping
+                    if let FinderCommand::ToggleFilter(f) = command {
+                        filters.toggle(f);
+                        match &mut source {
+                            FinderSource::Files(search) => match self.start_search(&filters) {
+                                Ok(s) => *search = s,
+                                Err(e) => break Err(e),
+                            },
+                            FinderSource::Grep(grep, _) => *grep = self.start_grep(&filters),
+                            FinderSource::Jump => (),
+                        }
+                    }
+
+                    // Content search results open the file at the matching line
+                    if let (FinderCommand::SelectEntry, FinderSource::Grep(_, hits)) =
+                        (command, &source)
+                    {
+                        if let Some(hit) = hits.get(self.window.finder.selected()) {
+                            let (file, line) = (self.curr_dir.join(hit.path()), hit.line());
+                            break self.open_file_at(file, line);
+                        }
+                        continue;
+                    }
+
+                    match self.execute_finder_command(command, kind == FinderType::Jump) {
+                        Ok(false) => (),
+                        done => break done.map(|_| ()),
+                    }
+                }
+            }
+
+            match &mut source {
+                FinderSource::Files(search) => {
+                    search.set_query(&self.window.finder.text(), self.window.finder.max_entries());
+                    match search.tick() {
+                        Ok(true) => self.window.finder.update_files(search.matches().to_vec()),
+                        Ok(false) => (),
+                        Err(e) => break Err(e),
+                    }
+                    self.window.finder.set_status(search.describe());
+                }
+                FinderSource::Grep(grep, hits) => {
+                    grep.set_query(&self.window.finder.text(), self.window.finder.max_entries());
+                    if grep.tick() {
+                        *hits = grep.hits();
+                        self.window
+                            .finder
+                            .update_files(hits.iter().map(GrepHit::to_match).collect());
+                    }
+                    self.window.finder.set_status(grep.describe());
+
+                    let selected = hits
+                        .get(self.window.finder.selected())
+                        .map(|h| (self.curr_dir.join(h.path()), h.line()));
+                    if selected != previewed {
+                        let shown = match &selected {
+                            Some((file, line)) => self.preview_file(file, *line),
+                            None => Err(io::Error::from(ErrorKind::NotFound).into()),
+                        };
+                        if shown.is_err() {
+                            self.window.preview.update_lines(Vec::new());
+                        }
+                        previewed = selected;
                     }
                 }
+                FinderSource::Jump => (),
             }
 
             self.draw()?
-        }
+        };
 
-        self.window.finder_mode(false);
+        self.window.finder_mode(false, kind);
         self.window.finder.update_files(Vec::new());
         self.window.finder.set_text(String::new());
         self.window.finder.reset();
 
-        Ok(())
+        // The preview was showing search results
+        if kind == FinderType::Grep && self.refresh_preview().is_err() {
+            self.window.preview.update_lines(Vec::new());
+        }
+
+        result
+    }
+
+    // Starts searching the contents of the current directory again with new filters
+    fn start_grep(&mut self, filters: &Filters) -> Grep {
+        self.window.finder.set_filters(filters.describe());
+        self.window.finder.update_files(Vec::new());
+
+        Grep::new(self.curr_dir.clone(), filters.clone())
+    }
+
+    // Starts walking the current directory again with new filters
+    fn start_search(&mut self, filters: &Filters) -> Result<Search> {
+        self.window.finder.set_filters(filters.describe());
+        self.window.finder.update_files(Vec::new());
+
+        let walker = Walker::start(self.curr_dir.clone(), filters)?;
+
+        Ok(Search::new(walker, self.config.finder_backend()))
     }
 
     fn omnibar_mode(&mut self, mode: OmnibarType) -> Result<()> {
+        if let OmnibarType::Rename = mode {
+            let entry = self.window.file_list.curr_entry();
+            if entry == "." || entry == ".." {
+                return Ok(());
+            }
+
+            // Start with the cursor before the extension, so the stem is easy to replace
+            let stem_len = Path::new(&entry)
+                .file_stem()
+                .map_or(0, |s| s.to_string_lossy().chars().count());
+            self.window.omnibar.set_text(entry);
+            self.window.omnibar.set_cursor(stem_len);
+        }
+
+        if let OmnibarType::Compress = mode {
+            let marked = self.window.file_list.marked_entries();
+            let entry = self.window.file_list.curr_entry();
+            if marked.is_empty() && (entry == "." || entry == "..") {
+                return Ok(());
+            }
+
+            // Named after what's being compressed, or the current directory if that's several
+            // entries. The cursor goes before the extension, so the name is easy to change.
+            let name = match marked.as_slice() {
+                [] => entry,
+                [m] => m.clone(),
+                _ => self
+                    .curr_dir
+                    .file_name()
+                    .map_or("archive".to_string(), |n| n.to_string_lossy().to_string()),
+            };
+            self.window.omnibar.set_text(format!("{}.zip", name));
+            self.window.omnibar.set_cursor(name.chars().count());
+        }
+
         self.window.omnibar_mode(true, mode);
 
         loop {
@@ -235,6 +443,14 @@ impl Browser {
                     if self.execute_omnibar_command(command, mode)? {
                         break;
                     }
+
+                    if let (
+                        OmnibarType::PatternRename,
+                        OmnibarCommand::Write(_) | OmnibarCommand::Backspace,
+                    ) = (mode, command)
+                    {
+                        self.preview_pattern_rename();
+                    }
                 }
             }
 
@@ -243,25 +459,103 @@ impl Browser {
 
         self.window.omnibar.set_text(String::new());
         self.window.omnibar_mode(false, mode);
+        self.window.file_list.preview_renames(HashMap::new());
+
+        Ok(())
+    }
+
+    fn open_with_mode(&mut self) -> Result<()> {
+        let entries = self.get_selected_entries()?;
+        let openers = self.config.get_openers(&entries[0]);
+
+        self.window.open_with_mode(true);
+        self.window
+            .open_with
+            .update_openers(openers.iter().map(Opener::name).collect());
+
+        let opener = loop {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    match self.finder_command(ke) {
+                        FinderCommand::Write(c) => {
+                            let mut text = self.window.open_with.text();
+                            text.push(c);
+                            self.window.open_with.set_text(text);
+                        }
+                        FinderCommand::Backspace => {
+                            let mut text = self.window.open_with.text();
+                            text.pop();
+                            self.window.open_with.set_text(text);
+                        }
+                        FinderCommand::EntryScroll(d) => self.window.open_with.scroll(d),
+                        FinderCommand::SelectEntry => {
+                            let text = self.window.open_with.text();
+
+                            // A typed command takes priority over the highlighted opener
+                            break if text.trim().is_empty() {
+                                openers.get(self.window.open_with.selected()).cloned()
+                            } else {
+                                Opener::from_input(&text)
+                            };
+                        }
+                        FinderCommand::Exit => break None,
+                        FinderCommand::ToggleFilter(_) | FinderCommand::None => (),
+                    }
+                }
+            }
+
+            self.draw()?;
+        };
+
+        self.window.open_with_mode(false);
+        self.window.open_with.reset();
+
+        if let Some(opener) = opener {
+            for entry in entries {
+                self.run_opener(&opener, &entry, 1)?;
+            }
+        }
 
         Ok(())
     }
 
     fn execute_file_list_command(&mut self, command: FileListCommand) -> Result<()> {
+        // Archives are browsed read-only
+        if command.modifies_dir() && vfs::split(&self.curr_dir).is_some() {
+            return Err(vfs::read_only().into());
+        }
+
         match &command {
             FileListCommand::EntryScroll(d) => self.window.file_list.scroll_entry(*d),
-            FileListCommand::SelectEntry => self.open_entry(self.get_canonical_entry()?)?,
+            FileListCommand::SelectEntry => self.select_entry()?,
             FileListCommand::HintMode => self.hint_mode()?,
-            FileListCommand::FinderMode(z) => self.finder_mode(*z)?,
+            FileListCommand::VisualMode => self.visual_mode()?,
+            FileListCommand::FinderMode(k) => self.finder_mode(*k)?,
             FileListCommand::OmnibarMode(m) => self.omnibar_mode(*m)?,
+            FileListCommand::OpenWith => self.open_with_mode()?,
+            FileListCommand::BulkRename => self.bulk_rename()?,
             FileListCommand::Yank(c) => self.yank(*c)?,
             FileListCommand::Paste => self.paste()?,
             FileListCommand::Delete(f) => self.delete(*f)?,
+            FileListCommand::Extract => self.extract()?,
+            FileListCommand::TrashMode => self.trash_mode()?,
+            FileListCommand::Undo => self.undo(false)?,
+            FileListCommand::Redo => self.undo(true)?,
+            FileListCommand::ToggleMark => self.window.file_list.toggle_mark(),
+            FileListCommand::MarkAll => self.window.file_list.mark_all(),
+            FileListCommand::InvertMarks => self.window.file_list.invert_marks(),
+            FileListCommand::ClearMarks => self.window.file_list.clear_marks(),
+            FileListCommand::PreviewScroll(d) => self.window.preview.scroll_page(*d),
             FileListCommand::Exit => self.exit = true,
-            FileListCommand::None | FileListCommand::ExitHint => (), // hint mode handles the latter binding
+            FileListCommand::None | FileListCommand::ExitHint => (), // hint and visual modes handle the latter
         };
 
         if command.should_refresh_preview() {
+            // Anything else may have changed what's being previewed
+            if !matches!(command, FileListCommand::PreviewScroll(_)) {
+                self.window.preview.reset_page();
+            }
+
             // May need to clear terminal if artifacts keep happening
             // self.terminal.clear()?;
             if self.refresh_preview().is_err() {
@@ -272,40 +566,48 @@ impl Browser {
         Ok(())
     }
 
-    fn execute_finder_command(&mut self, command: FinderCommand, zoxide: bool) -> Result<bool> {
+    fn execute_finder_command(&mut self, command: FinderCommand, jump: bool) -> Result<bool> {
         match &command {
             FinderCommand::Write(c) => {
                 let mut text = self.window.finder.text();
                 text.push(*c);
-                self.window
-                    .finder
-                    .update_files(self.find(text.clone(), zoxide)?);
-                self.window.finder.set_text(text);
+                self.update_finder_text(text, jump)?;
             }
             FinderCommand::Backspace => {
                 let mut text = self.window.finder.text();
-                if !text.is_empty() {
-                    text.remove(text.len() - 1);
-                    self.window
-                        .finder
-                        .update_files(self.find(text.clone(), zoxide)?);
-                    self.window.finder.set_text(text);
+                if text.pop().is_some() {
+                    self.update_finder_text(text, jump)?;
                 }
             }
             FinderCommand::SelectEntry => {
-                self.open_entry(self.window.finder.selection().into())?;
-                return Ok(true);
+                // Results may not have come in yet
+                if let Some(selection) = self.window.finder.selection() {
+                    self.open_entry(selection.into())?;
+                    return Ok(true);
+                }
             }
             FinderCommand::EntryScroll(d) => {
                 self.window.finder.scroll(*d);
             }
             FinderCommand::Exit => return Ok(true),
-            FinderCommand::None => (),
+            // Finder mode handles filters itself, since they restart the search
+            FinderCommand::ToggleFilter(_) | FinderCommand::None => (),
         }
 
         Ok(false)
     }
 
+    // File results are picked up by the search on the next frame, directories to jump to are
+    // looked up straight away
+    fn update_finder_text(&mut self, text: String, jump: bool) -> Result<()> {
+        if jump {
+            self.window.finder.update_files(self.jump(text.clone())?);
+        }
+        self.window.finder.set_text(text);
+
+        Ok(())
+    }
+
     fn execute_omnibar_command(
         &mut self,
         command: OmnibarCommand,
@@ -314,44 +616,33 @@ impl Browser {
         let mut submit = false;
 
         match command {
-            OmnibarCommand::Write(c) => {
-                let mut text = self.window.omnibar.text().clone();
-                text.push(c);
-
-                self.window.omnibar.set_text(text);
-            }
-            OmnibarCommand::Backspace => {
-                let mut text = self.window.omnibar.text().clone();
-                text.truncate(text.len().saturating_sub(1));
-
-                self.window.omnibar.set_text(text);
-            }
+            OmnibarCommand::Write(c) => self.window.omnibar.insert(c),
+            OmnibarCommand::Backspace => self.window.omnibar.backspace(),
+            OmnibarCommand::CursorMove(r) => self.window.omnibar.move_cursor(r),
             OmnibarCommand::Submit => submit = true,
-            OmnibarCommand::Exit => return Ok(true),
+            OmnibarCommand::Exit => {
+                self.window.status.clear();
+                return Ok(true);
+            }
             OmnibarCommand::None => (),
         }
 
         if submit {
             let mut newpath = self.curr_dir.clone();
             newpath.push(PathBuf::from(self.window.omnibar.text()));
-            match mode {
-                OmnibarType::Rename => {
-                    let entry = self.get_canonical_entry()?;
-                    if entry.is_dir() {
-                        panic!("Can't rename a directory")
-                    }
 
-                    fs::copy(&entry, newpath)?;
-                    fs::remove_file(entry)?;
-                }
-                OmnibarType::Touch => {
-                    fs::File::create(newpath)?;
-                }
-                OmnibarType::Mkdir => {
-                    fs::create_dir(newpath)?;
-                }
+            // Failures are shown to the user, who can then fix the name or back out
+            if let Err(e) = self.submit_omnibar(newpath, mode) {
+                self.window.status.error(e.to_string());
+                return Ok(false);
             }
 
+            if !matches!(
+                mode,
+                OmnibarType::MarkGlob | OmnibarType::PatternRename | OmnibarType::Compress
+            ) {
+                self.window.status.clear();
+            }
             self.change_directory(self.curr_dir.clone())?;
 
             Ok(true)
@@ -360,12 +651,136 @@ impl Browser {
         }
     }
 
+    fn submit_omnibar(&mut self, newpath: PathBuf, mode: OmnibarType) -> Result<()> {
+        match mode {
+            OmnibarType::Rename => {
+                let entry = self.get_canonical_entry()?;
+                if newpath == entry {
+                    return Ok(());
+                }
+
+                // `fs::rename` silently replaces existing files
+                fsops::ensure_free(&newpath)?;
+
+                fs::rename(&entry, &newpath)?;
+                self.journal.record(Operation::Rename {
+                    from: entry,
+                    to: newpath,
+                })?;
+            }
+            OmnibarType::Touch => {
+                fs::File::create_new(&newpath)?;
+                self.journal.record(Operation::Touch { path: newpath })?;
+            }
+            OmnibarType::Mkdir => {
+                fs::create_dir(&newpath)?;
+                self.journal.record(Operation::Mkdir { path: newpath })?;
+            }
+            OmnibarType::MarkGlob => {
+                let glob = Glob::new(self.window.omnibar.text())?.compile_matcher();
+                let count = self.window.file_list.mark_glob(&glob);
+                self.window.status.info(format!("Marked {} entries", count));
+            }
+            OmnibarType::PatternRename => {
+                let plan = self.plan_pattern_rename()?;
+
+                let mut operations = Vec::new();
+                let result = plan.apply(&mut operations);
+
+                self.journal.record(Operation::Batch { operations })?;
+                if result.is_err() {
+                    // Some of the renames may have happened
+                    self.change_directory(self.curr_dir.clone())?;
+                    return result;
+                }
+
+                self.window.file_list.clear_marks();
+                self.window
+                    .status
+                    .info(format!("Renamed {} entries", plan.len()));
+            }
+            OmnibarType::Compress => {
+                fsops::ensure_free(&newpath)?;
+
+                let sources = self.get_selected_entries()?;
+                let (from, to) = (sources.clone(), newpath.clone());
+                self.run_with_progress("Compressing", move |p| vfs::compress(&from, &to, p))?;
+
+                let count = sources.len();
+                self.journal.record(Operation::Compress {
+                    sources,
+                    to: newpath,
+                })?;
+                self.window.file_list.clear_marks();
+                self.window.status.info(format!(
+                    "Compressed {} entries into {}",
+                    count,
+                    self.window.omnibar.text()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Works out what the pattern in the omnibar would rename. It applies to the marked entries,
+    // or to every entry if nothing is marked (substitutions only rename names they match).
+    fn plan_pattern_rename(&self) -> Result<RenamePlan> {
+        let pattern = RenamePattern::parse(self.window.omnibar.text())?;
+
+        let mut names = self.window.file_list.marked_entries();
+        if names.is_empty() {
+            names = self.window.file_list.entries();
+        }
+
+        let (old, new) = pattern.apply_all(&names);
+
+        RenamePlan::new(self.curr_dir.clone(), &old, &new)
+    }
+
+    // Shows the renames the current pattern would make in the file list, or why it can't
+    fn preview_pattern_rename(&mut self) {
+        let mut renames = HashMap::new();
+
+        if self.window.omnibar.text().is_empty() {
+            self.window.status.clear();
+        } else {
+            match self.plan_pattern_rename() {
+                Ok(plan) => {
+                    renames.extend(plan.renames().iter().cloned());
+                    self.window.status.info(format!("{} renames", plan.len()));
+                }
+                Err(e) => self.window.status.error(e.to_string()),
+            }
+        }
+
+        self.window.file_list.preview_renames(renames);
+    }
+
     fn change_directory(&mut self, new_dir: PathBuf) -> Result<()> {
-        self.curr_dir = new_dir
-            .canonicalize()
-            .expect("Trying to cd to non-existent directory");
+        // Paths inside archives don't exist as far as the OS is concerned, so they can't be
+        // canonicalized or used as the working directory
+        let archive = vfs::split(&new_dir).map(|(a, _)| a);
+        let new_dir = match archive {
+            Some(_) => new_dir,
+            None => new_dir
+                .canonicalize()
+                .expect("Trying to cd to non-existent directory"),
+        };
+
+        if new_dir != self.curr_dir {
+            self.window.file_list.clear_marks();
+            if archive.is_none() {
+                self.frecency.visit(&new_dir)?;
+            }
+        }
+        self.curr_dir = new_dir;
 
-        set_current_dir(self.curr_dir.as_path()).expect("Unable to change working directory");
+        let working_dir = match &archive {
+            Some(a) => a.parent().expect("Archive has no parent directory"),
+            None => self.curr_dir.as_path(),
+        };
+        set_current_dir(working_dir).expect("Unable to change working directory");
 
         let sorted_files = fetch_files(self.curr_dir.as_path())?;
 
@@ -377,16 +792,72 @@ impl Browser {
         Ok(())
     }
 
+    fn run_opener(&mut self, opener: &Opener, file: &Path, line: usize) -> Result<()> {
+        // Files in archives are opened from a temporary copy
+        let file = &vfs::materialize(file)?;
+
+        if opener.terminal() {
+            self.run_foreground(opener.command_at(file, line))
+        } else {
+            opener
+                .command_at(file, line)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+
+            Ok(())
+        }
+    }
+
+    // Hands the terminal over to `command` until it exits, then redraws everything
+    fn run_foreground(&mut self, mut command: Command) -> Result<()> {
+        tui::restore()?;
+        let status = command.status();
+        self.terminal = tui::init()?;
+        self.terminal.clear()?;
+
+        // The program may have changed the directory contents
+        self.change_directory(self.curr_dir.clone())?;
+        self.draw()?;
+
+        status?;
+
+        Ok(())
+    }
+
     fn open_file(&mut self, file: PathBuf) -> Result<()> {
-        let program = "code";
+        self.open_file_at(file, 1)
+    }
 
-        Command::new(program).arg(file).spawn()?;
+    fn open_file_at(&mut self, file: PathBuf, line: usize) -> Result<()> {
+        if let Some(opener) = self.config.get_opener(&file) {
+            self.run_opener(&opener, &file, line)?;
+        }
+
+        Ok(())
+    }
+
+    // Opens every marked file if there are any, otherwise the current entry
+    fn select_entry(&mut self) -> Result<()> {
+        // Marked files are only opened together from one of them, so directories can still be
+        // entered while anything is marked
+        if !self.window.file_list.curr_marked() {
+            return self.open_entry(self.get_canonical_entry()?);
+        }
+
+        for entry in self.get_selected_entries()? {
+            if !entry.is_dir() {
+                self.open_file(entry)?;
+            }
+        }
 
         Ok(())
     }
 
     fn open_entry(&mut self, entry: PathBuf) -> Result<()> {
-        if entry.as_path().is_dir() {
+        // Archives are entered like directories
+        if entry.as_path().is_dir() || vfs::is_dir(&entry) {
             self.change_directory(entry)?;
         } else {
             self.open_file(entry)?;
@@ -395,155 +866,582 @@ impl Browser {
         Ok(())
     }
 
-    fn find(&self, text: String, zoxide: bool) -> Result<Vec<String>> {
-        if zoxide {
-            let command = Command::new("zoxide")
-                .arg("query")
-                .arg("--list")
-                .arg(text)
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .spawn()?;
+    fn jump(&self, text: String) -> Result<Vec<Match>> {
+        match self.config.jump_backend() {
+            JumpBackend::Native => Ok(self.frecency.query(&text, self.window.finder.max_entries())),
+            JumpBackend::Zoxide => self.zoxide(text),
+        }
+    }
 
-            Ok(String::from_utf8(command.wait_with_output()?.stdout)
-                .expect("Couldn't read zoxide output")
-                .lines()
-                .take(self.window.finder.max_entries())
-                .map(Into::into)
-                .collect())
-        } else {
-            let path = current_dir().expect("Unable to access current dir");
-
-            let items = Walk::new(PathBuf::from(&path))
-                .map(|r| {
-                    r.expect("Failed to process file")
-                        .into_path()
-                        .strip_prefix(&path)
-                        .expect("Found file not in curr directory")
-                        .to_string_lossy()
-                        .to_string()
-                })
-                .filter(|i| !i.is_empty()) // Walk has directory itself as member, filter that out
-                .collect::<Vec<_>>();
+    fn zoxide(&self, text: String) -> Result<Vec<Match>> {
+        let command = Command::new("zoxide")
+            .arg("query")
+            .arg("--list")
+            .arg(text)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
 
-            if text.is_empty() {
-                return Ok(items);
-            }
+        Ok(String::from_utf8(command.wait_with_output()?.stdout)
+            .expect("Couldn't read zoxide output")
+            .lines()
+            .take(self.window.finder.max_entries())
+            .map(|l| Match::plain(l.into()))
+            .collect())
+    }
 
-            let mut command = Command::new("fzf")
-                .arg("-f")
-                .arg(&text)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()?;
+    fn get_canonical_entry(&self) -> Result<PathBuf> {
+        let entry = self.window.file_list.curr_entry();
+        let mut dir = self.curr_dir.join(&entry);
+
+        // Other entries aren't canonicalized so that symlinks are acted on rather than followed
+        if entry == "." || entry == ".." {
+            dir = match vfs::split(&self.curr_dir) {
+                // Paths inside archives can't be canonicalized, but they never have `..` in them
+                Some(_) if entry == "." => self.curr_dir.clone(),
+                Some(_) => self
+                    .curr_dir
+                    .parent()
+                    .unwrap_or(&self.curr_dir)
+                    .to_path_buf(),
+                None => dir.canonicalize()?,
+            };
+        }
 
-            for i in items {
-                let stdin = command
-                    .stdin
-                    .as_mut()
-                    .expect("Unable to access stdin for fzf");
-                stdin.write_fmt(format_args!("{}\n", i))?;
-                stdin.flush()?;
-            }
+        Ok(dir)
+    }
+
+    // Commands on entries do nothing on `.` and `..`, which can't be marked. Acting on them
+    // would mean acting on the current directory (or `/` itself) from inside it.
+    fn dot_entry_selected(&self) -> bool {
+        let entry = self.window.file_list.curr_entry();
+        self.window.file_list.marked_entries().is_empty() && (entry == "." || entry == "..")
+    }
 
-            Ok(String::from_utf8(command.wait_with_output()?.stdout)
-                .expect("Unable to read fzf output")
-                .lines()
-                .take(self.window.finder.max_entries())
-                .map(Into::into)
-                .collect())
+    /// Returns the marked entries, or the current entry if nothing is marked
+    fn get_selected_entries(&self) -> Result<Vec<PathBuf>> {
+        let marked = self.window.file_list.marked_entries();
+
+        if marked.is_empty() {
+            Ok(vec![self.get_canonical_entry()?])
+        } else {
+            Ok(marked.into_iter().map(|m| self.curr_dir.join(m)).collect())
         }
     }
 
-    fn get_canonical_entry(&self) -> Result<PathBuf> {
-        let mut dir = current_dir()?;
-        dir.push(PathBuf::from(self.window.file_list.curr_entry()));
-        dir = dir.canonicalize()?;
+    // Lets the user edit the selected names in their editor, then renames to match
+    fn bulk_rename(&mut self) -> Result<()> {
+        if self.dot_entry_selected() {
+            return Ok(());
+        }
 
-        Ok(dir)
+        let old = self
+            .get_selected_entries()?
+            .iter()
+            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .collect::<Vec<_>>();
+        if old.is_empty() {
+            return Ok(());
+        }
+
+        let dir = fsops::private_temp_dir()?;
+        let file = dir.join("rename.txt");
+
+        let edited = fs::write(&file, old.join("\n") + "\n")
+            .map_err(Into::into)
+            .and_then(|_| self.run_foreground(Opener::editor().command(&file)))
+            .and_then(|_| Ok(fs::read_to_string(&file)?));
+        fs::remove_dir_all(&dir)?;
+
+        let new = edited?
+            .lines()
+            .map(|l| l.trim_end_matches('\r').to_string())
+            .collect::<Vec<_>>();
+
+        let plan = RenamePlan::new(self.curr_dir.clone(), &old, &new)?;
+        if plan.is_empty() {
+            return Ok(());
+        }
+
+        let title = format!("Apply {} renames?", plan.len());
+        if !self.confirm(title, plan.preview())? {
+            return Ok(());
+        }
+
+        let mut operations = Vec::new();
+        let result = plan.apply(&mut operations);
+
+        self.journal.record(Operation::Batch { operations })?;
+        self.window.file_list.clear_marks();
+        self.change_directory(self.curr_dir.clone())?;
+
+        result
     }
 
     fn yank(&mut self, cut: bool) -> Result<()> {
-        let path = self.get_canonical_entry()?;
-
-        if path.is_dir() {
+        if self.dot_entry_selected() {
             return Ok(());
         }
 
-        self.window
-            .clipboard
-            .push(ClipboardEntry::new(self.get_canonical_entry()?, cut));
+        for entry in self.get_selected_entries()? {
+            self.window.clipboard.push(ClipboardEntry::new(entry, cut));
+        }
+
+        self.window.file_list.clear_marks();
 
         Ok(())
     }
 
+    // Asks what to do about `src` colliding with `dst`. Returns `None` if the paste is cancelled.
+    fn conflict_mode(&mut self, src: &Path, dst: &Path) -> Result<Option<ConflictCommand>> {
+        let describe = |p: &Path| match fs::symlink_metadata(p) {
+            Ok(m) => format!(
+                "{}, modified {}",
+                if m.is_dir() {
+                    "directory".to_string()
+                } else {
+                    format_size(m.len())
+                },
+                m.modified().map(format_time).unwrap_or_default()
+            ),
+            Err(_) => "unknown".to_string(),
+        };
+
+        let mut lines = vec![
+            format!(
+                "{} already exists",
+                dst.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            String::new(),
+            format!("Pasting:  {}", describe(src)),
+            format!("Existing: {}", describe(dst)),
+            String::new(),
+        ];
+        lines.append(&mut self.config.describe_conflict_bindings());
+
+        self.window.conflict.update_lines(lines);
+        self.window.conflict_mode(true);
+
+        let command = loop {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    if ke.kind == KeyEventKind::Press {
+                        match self.config.get_conflict_command(ke.code) {
+                            Some(ConflictCommand::Exit) => break None,
+                            Some(ConflictCommand::None) | None => (),
+                            Some(c) => break Some(c),
+                        }
+                    }
+                }
+            }
+
+            self.draw()?;
+        };
+
+        self.window.conflict_mode(false);
+
+        Ok(command)
+    }
+
     fn paste(&mut self) -> Result<()> {
-        for ce in self.window.clipboard.get_files() {
-            let path = ce.file().canonicalize()?;
+        // Every conflict is resolved before anything is touched, so cancelling leaves both the
+        // filesystem and the clipboard as they were
+        let mut plan: Vec<(ClipboardEntry, PathBuf, bool)> = Vec::new(); // (entry, dest, overwrite)
+        let mut choice_for_all = None;
+
+        for ce in self.window.clipboard.get_files().clone() {
+            let path = ce.file().clone();
             let mut new_path = self.curr_dir.clone();
             new_path.push(PathBuf::from(
-                path.file_name().expect("Trying to copy directory"),
+                path.file_name().expect("Trying to copy root directory"),
             ));
 
-            fs::copy(&path, new_path)?;
+            let planned = |p: &Path, plan: &Vec<(ClipboardEntry, PathBuf, bool)>| {
+                plan.iter().any(|(_, d, _)| d == p)
+            };
+
+            let mut overwrite = false;
+            if fs::symlink_metadata(&new_path).is_ok() || planned(&new_path, &plan) {
+                let command = match choice_for_all {
+                    Some(c) => c,
+                    None => match self.conflict_mode(&path, &new_path)? {
+                        Some(c) => c,
+                        None => return Ok(()),
+                    },
+                };
+
+                if command.applies_to_all() {
+                    choice_for_all = Some(command);
+                }
 
-            if ce.cut() {
-                fs::remove_file(path)?;
+                match command {
+                    // Overwriting something with itself (or a parent of itself) would destroy it
+                    ConflictCommand::Overwrite(_) if path.starts_with(&new_path) => continue,
+                    ConflictCommand::Overwrite(_) => overwrite = true,
+                    ConflictCommand::Rename(_) => {
+                        new_path =
+                            fsops::unique_path(&new_path, path.is_dir(), |p| planned(p, &plan))
+                    }
+                    _ => continue,
+                }
             }
+
+            plan.push((ce, new_path, overwrite));
         }
 
+        let mut operations = Vec::new();
+        let result = self.execute_paste(plan, &mut operations);
+
+        // Whatever was done before a failure can still be undone
+        self.journal.record(Operation::Batch { operations })?;
+
         // Forces reload of files
         self.change_directory(self.curr_dir.clone())?;
 
-        self.window.clipboard.clear();
+        if result.is_ok() {
+            self.window.clipboard.clear();
+        }
+        result
+    }
+
+    fn execute_paste(
+        &mut self,
+        plan: Vec<(ClipboardEntry, PathBuf, bool)>,
+        operations: &mut Vec<Operation>,
+    ) -> Result<()> {
+        for (ce, new_path, overwrite) in plan {
+            // Replaced files go to the trash so the paste can be undone
+            if overwrite {
+                operations.push(Operation::Trash {
+                    entry: self.trash.trash(&new_path)?,
+                });
+            }
+
+            let from = ce.file().clone();
+            if ce.cut() {
+                fsops::move_path(&from, &new_path)?;
+                operations.push(Operation::Move {
+                    from: from.clone(),
+                    to: new_path,
+                });
+            } else {
+                vfs::copy(&from, &new_path)?;
+                operations.push(Operation::Copy {
+                    from: from.clone(),
+                    to: new_path,
+                });
+            }
+
+            // Entries already pasted leave the clipboard, so pasting again after a failure only
+            // retries the rest, and doesn't look for cut files where they no longer are
+            self.window.clipboard.remove(&from);
+        }
+
+        Ok(())
+    }
+
+    // Asks a yes/no question, returning true if the answer was yes
+    fn confirm(&mut self, title: String, lines: Vec<String>) -> Result<bool> {
+        let hint = self.config.describe_confirm_bindings();
+        self.window.confirm.ask(title, lines, hint);
+        self.window.confirm_mode(true);
+
+        let answer = loop {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    if ke.kind == KeyEventKind::Press {
+                        match self.config.get_confirm_command(ke.code) {
+                            Some(ConfirmCommand::Yes) => break true,
+                            Some(ConfirmCommand::No) => break false,
+                            Some(ConfirmCommand::None) | None => (),
+                        }
+                    }
+                }
+            }
+
+            self.draw()?;
+        };
+
+        self.window.confirm_mode(false);
+
+        Ok(answer)
+    }
+
+    // Lists `paths` along with their sizes and the total, for confirmation dialogs
+    fn describe_removal(paths: &[PathBuf]) -> Vec<String> {
+        // Big trees are only partly added up, so the dialog doesn't keep the UI waiting
+        let mut budget = fsops::USAGE_BUDGET;
+        let mut total = 0;
+        let mut partial = false;
+        let mut lines = Vec::new();
+
+        let size = |size: u64, partial: bool| {
+            let over = if partial { "over " } else { "" };
+            format!("{}{}", over, format_size(size))
+        };
+        for p in paths {
+            let dir = p.is_dir();
+            let usage = fsops::disk_usage_within(p, &mut budget);
+            let cut_short = dir && budget == 0;
+            total += usage;
+            partial |= cut_short;
+
+            let kind = if dir { "/" } else { "" };
+            lines.push(format!(
+                "{}{}  ({})",
+                p.to_string_lossy(),
+                kind,
+                size(usage, cut_short)
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("Total: {}", size(total, partial)));
+
+        lines
+    }
+
+    fn delete(&mut self, force: bool) -> Result<()> {
+        if self.dot_entry_selected() {
+            return Ok(());
+        }
+
+        let paths = self.get_selected_entries()?;
+
+        let title = if force {
+            "Delete permanently?"
+        } else {
+            "Move to trash?"
+        };
+        let lines = Self::describe_removal(&paths);
+        if !self.confirm(title.to_string(), lines)? {
+            return Ok(());
+        }
+
+        let mut operations = Vec::new();
+        let mut result = Ok(());
+        for path in paths {
+            result = if force {
+                fsops::remove_path(&path)
+            } else {
+                self.trash
+                    .trash(&path)
+                    .map(|entry| operations.push(Operation::Trash { entry }))
+            };
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.journal.record(Operation::Batch { operations })?;
+        self.window.file_list.clear_marks();
+        self.change_directory(self.curr_dir.clone())?;
+
+        result
+    }
+
+    // Unpacks the selected archive into a new directory next to it
+    fn extract(&mut self) -> Result<()> {
+        let archive = self.get_canonical_entry()?;
+        if Format::detect(&archive).is_none() || !archive.is_file() {
+            return Err(vfs::not_archive().into());
+        }
+
+        // Only numbered if the archive's name is taken
+        let mut to = vfs::extract_dir(&archive);
+        if fs::symlink_metadata(&to).is_ok() {
+            to = fsops::unique_path(&to, true, |_| false);
+        }
+
+        let (from, dst) = (archive.clone(), to.clone());
+        self.run_with_progress("Extracting", move |p| vfs::extract_all(&from, &dst, p))?;
+
+        let name = to
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        self.journal.record(Operation::Extract { archive, to })?;
+        self.change_directory(self.curr_dir.clone())?;
+        self.window.status.info(format!("Extracted to {}", name));
+
         Ok(())
     }
-    
-        
-    fn delete(&mut self, force: bool) ->  Result<()> {
-        let path = &self.get_canonical_entry()?;
 
-        if path.is_file() {
-            fs::remove_file(path)?;
+    // Runs `job` on another thread, showing how far it's got in the status line until it's done.
+    // The omnibar's exit key cancels it.
+    fn run_with_progress(
+        &mut self,
+        action: &str,
+        job: impl FnOnce(&Progress) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let progress = Progress::default();
+        let handle = {
+            let progress = progress.clone();
+            thread::spawn(move || job(&progress))
+        };
+
+        while !handle.is_finished() {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    if let OmnibarCommand::Exit = self.omnibar_command(ke) {
+                        progress.cancel();
+                    }
+                }
+            }
+
+            self.window.status.info(format!(
+                "{}… {}/{} entries",
+                action,
+                progress.done(),
+                progress.total()
+            ));
+            self.draw()?;
         }
-        if path.is_dir() && force {
-            fs::remove_dir_all(path)?;
+
+        self.window.status.clear();
+        handle.join().expect("Archive thread panicked")
+    }
+
+    fn undo(&mut self, redo: bool) -> Result<()> {
+        let result = if redo {
+            self.journal.redo(&self.trash)
+        } else {
+            self.journal.undo(&self.trash)
+        };
+
+        // Refresh even on failure, since part of a batch may have been reversed
+        self.change_directory(self.curr_dir.clone())?;
+
+        match result? {
+            Some(d) if redo => self.window.status.info(format!("Redid {}", d)),
+            Some(d) => self.window.status.info(format!("Undid {}", d)),
+            None if redo => self.window.status.info("Nothing to redo".to_string()),
+            None => self.window.status.info("Nothing to undo".to_string()),
         }
-        
+
         Ok(())
     }
 
+    fn refresh_trash(&mut self) -> Result<Vec<TrashEntry>> {
+        let entries = self.trash.list()?;
+        self.window.trash.update_entries(
+            entries
+                .iter()
+                .map(|e| {
+                    (
+                        e.deleted().to_string(),
+                        e.original().to_string_lossy().to_string(),
+                    )
+                })
+                .collect(),
+        );
+
+        Ok(entries)
+    }
+
+    fn trash_mode(&mut self) -> Result<()> {
+        let mut entries = self.refresh_trash()?;
+        self.window.trash_mode(true);
+
+        loop {
+            if event::poll(Duration::from_millis(16))? {
+                if let Event::Key(ke) = event::read()? {
+                    if ke.kind != KeyEventKind::Press {
+                        continue;
+                    }
+
+                    let command = self
+                        .config
+                        .get_trash_command(ke.code)
+                        .unwrap_or(TrashCommand::None);
+                    let selected = self.window.trash.selected().map(|i| &entries[i]);
+
+                    let result = match (command, selected) {
+                        (TrashCommand::EntryScroll(d), _) => {
+                            self.window.trash.scroll(d);
+                            Ok(())
+                        }
+                        (TrashCommand::Restore, Some(e)) => self.trash.restore(e),
+                        (TrashCommand::Purge, Some(e)) => {
+                            let title = "Delete permanently?".to_string();
+                            let lines = vec![e.original().to_string_lossy().to_string()];
+
+                            if self.confirm(title, lines)? {
+                                self.trash.purge(e)
+                            } else {
+                                Ok(())
+                            }
+                        }
+                        (TrashCommand::Exit, _) => break,
+                        _ => Ok(()),
+                    };
+
+                    match result {
+                        Ok(()) => self.window.status.clear(),
+                        Err(e) => self.window.status.error(e.to_string()),
+                    }
+
+                    if matches!(command, TrashCommand::Restore | TrashCommand::Purge) {
+                        entries = self.refresh_trash()?;
+                    }
+                }
+            }
+
+            self.draw()?;
+        }
+
+        self.window.trash_mode(false);
+        self.window.status.clear();
+
+        // Restored items may have landed in the current directory
+        self.change_directory(self.curr_dir.clone())?;
+
+        Ok(())
+    }
 
     fn refresh_preview(&mut self) -> Result<()> {
-        let path = PathBuf::from(self.window.file_list.curr_entry()).canonicalize()?;
+        let path = self.get_canonical_entry()?;
 
-        if !path.is_file() {
+        // Entries in archives would have to be extracted first
+        if vfs::inside(&path) {
             self.window.preview.update_lines(Vec::new());
 
             return Ok(());
         }
 
-        let command = Command::new("bat")
-            .arg("-P")
-            .arg("--wrap=never")
-            .arg(format!(
-                "--line-range=1:{}",
-                self.window.preview.max_lines()
-            ))
-            .arg("--number")
-            .arg(path)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .spawn()?;
+        self.preview_file(&path, 1)
+    }
 
-        // Processing it like this lets us mostly catch utf-8 issues
-        self.window.preview.update_lines(
-            String::from_utf8(command.wait_with_output()?.stdout)
-                .unwrap_or_default()
-                .lines()
-                .map(String::from)
-                .collect(),
-        );
+    // Previews `path` with the previewer configured for it, at `line` if it's shown as text
+    fn preview_file(&mut self, path: &Path, line: usize) -> Result<()> {
+        let Some(previewer) = self.config.get_previewer(path) else {
+            self.window.preview.update_lines(Vec::new());
+
+            return Ok(());
+        };
+
+        if !previewer.pages() {
+            self.window.preview.reset_page();
+        }
+
+        let mut pane = Pane {
+            line,
+            page: self.window.preview.page(),
+            width: self.window.preview.width(),
+            height: self.window.preview.max_lines(),
+        };
+        let mut lines = previewer.preview(path, &pane, &self.highlighter)?;
+
+        // Scrolled past the end of the file
+        if lines.is_empty() && pane.page > 0 {
+            self.window.preview.scroll_page(false);
+            pane.page -= 1;
+            lines = previewer.preview(path, &pane, &self.highlighter)?;
+        }
+
+        self.window.preview.update_lines(lines);
 
         Ok(())
     }
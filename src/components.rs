@@ -1,15 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use bimap::BiHashMap;
+use chrono::{DateTime, Local};
+use globset::GlobMatcher;
 use ratatui::{
     crossterm::style::Color,
+    layout::Alignment,
     prelude::{Buffer, Rect},
     style::{Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{block::Title, Block, Paragraph, Widget},
+    widgets::{
+        block::{Position, Title},
+        Block, Clear, Paragraph, Widget,
+    },
 };
 
-use crate::config::OmnibarType;
+use crate::{
+    config::{FinderType, OmnibarType},
+    fuzzy::Match,
+};
 
 pub const BLOCK_LINES: u16 = 2;
 
@@ -24,6 +37,35 @@ pub const CONTROLS_HEIGHT: u16 = BLOCK_LINES + CONTROLS_LINES;
 
 pub const TOTAL_USED_LINES: u16 = BLOCK_LINES + CURR_DIR_HEIGHT + CONTROLS_HEIGHT;
 
+/// Formats a byte count like `1.5 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Formats a timestamp in local time, like `2024-08-30 17:05`
+pub fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+// How entries are coloured in the file list
+pub const DIR_COLOR: Color = Color::DarkYellow;
+pub const FILE_COLOR: Color = Color::Cyan;
+
 #[derive(Clone)]
 pub struct File {
     name: String,
@@ -48,6 +90,9 @@ pub struct FileList {
     max_entries: usize,
     hint_mode: bool,
     hint_choices: BiHashMap<usize, String>,
+    marked: HashSet<String>,
+    anchor: Option<usize>, // where visual mode started, as an index into files
+    renames: HashMap<String, String>, // pending renames shown next to the old names
     visible: bool,
 }
 
@@ -79,6 +124,10 @@ impl FileList {
         self.files = files;
         self.selected = 0;
         self.scroll = 0;
+
+        // Marks only survive for entries that still exist
+        let names = self.files.iter().map(|f| &f.name).collect::<HashSet<_>>();
+        self.marked.retain(|m| names.contains(m));
     }
 
     pub fn set_max_entries(&mut self, max_entries: usize) {
@@ -122,7 +171,110 @@ impl FileList {
     }
 
     pub fn curr_entry(&self) -> String {
-        self.files[self.selected].name.clone()
+        self.files[self.scroll + self.selected].name.clone()
+    }
+
+    // `.` and `..` can't be marked, since bulk operations on them make no sense
+    fn markable(name: &str) -> bool {
+        name != "." && name != ".."
+    }
+
+    pub fn curr_marked(&self) -> bool {
+        self.marked.contains(&self.curr_entry())
+    }
+
+    /// Toggles the mark on the current entry and moves down to the next one
+    pub fn toggle_mark(&mut self) {
+        let entry = self.curr_entry();
+        if Self::markable(&entry) && !self.marked.remove(&entry) {
+            self.marked.insert(entry);
+        }
+
+        self.scroll_entry(true);
+    }
+
+    pub fn mark_all(&mut self) {
+        self.marked = self
+            .files
+            .iter()
+            .map(|f| f.name.clone())
+            .filter(|n| Self::markable(n))
+            .collect();
+    }
+
+    pub fn invert_marks(&mut self) {
+        self.marked = self
+            .files
+            .iter()
+            .map(|f| f.name.clone())
+            .filter(|n| Self::markable(n) && !self.marked.contains(n))
+            .collect();
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Marks every entry whose name matches `glob`, returning how many matched
+    pub fn mark_glob(&mut self, glob: &GlobMatcher) -> usize {
+        let matches = self
+            .files
+            .iter()
+            .map(|f| f.name.clone())
+            .filter(|n| Self::markable(n) && glob.is_match(n))
+            .collect::<Vec<_>>();
+
+        let count = matches.len();
+        self.marked.extend(matches);
+
+        count
+    }
+
+    pub fn start_visual(&mut self) {
+        self.anchor = Some(self.scroll + self.selected);
+    }
+
+    // Indices of the first and last entries between the anchor and the cursor
+    fn visual_range(&self) -> Option<(usize, usize)> {
+        let curr = self.scroll + self.selected;
+        self.anchor.map(|a| (a.min(curr), a.max(curr)))
+    }
+
+    /// Ends visual mode, marking the selected range if `confirm` is true
+    pub fn end_visual(&mut self, confirm: bool) {
+        if let (true, Some((start, end))) = (confirm, self.visual_range()) {
+            let names = self.files[start..=end]
+                .iter()
+                .map(|f| f.name.clone())
+                .filter(|n| Self::markable(n))
+                .collect::<Vec<_>>();
+            self.marked.extend(names);
+        }
+
+        self.anchor = None;
+    }
+
+    /// Returns every entry except `.` and `..`, in the order they're listed
+    pub fn entries(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|f| f.name.clone())
+            .filter(|n| Self::markable(n))
+            .collect()
+    }
+
+    /// Shows each entry in `renames` alongside the name it would be renamed to
+    pub fn preview_renames(&mut self, renames: HashMap<String, String>) {
+        self.renames = renames;
+    }
+
+    /// Returns the marked entries, in the order they're listed
+    pub fn marked_entries(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|f| self.marked.contains(&f.name))
+            .map(|f| f.name.clone())
+            .collect()
     }
 }
 
@@ -155,13 +307,36 @@ impl Widget for FileList {
                                     Span::styled(f.name(), Style::new().fg(f.color.into())),
                                 ])
                             }
-                        } else if i == self.selected {
-                            Line::from(vec!["   ".on_black(), f.name.clone().black().on_white()])
                         } else {
-                            Line::from(vec![
-                                "   ".on_black(),
-                                Span::styled(f.name(), Style::new().fg(f.color.into())),
-                            ])
+                            let in_range = self.visual_range().is_some_and(|(start, end)| {
+                                (start..=end).contains(&(i + self.scroll))
+                            });
+                            let marked = self.marked.contains(&f.name);
+                            let marker = if marked {
+                                " * ".magenta().bold().on_black()
+                            } else {
+                                "   ".on_black()
+                            };
+
+                            let mut line = if i == self.selected {
+                                Line::from(vec![marker, f.name.clone().black().on_white()])
+                            } else if in_range {
+                                Line::from(vec![marker, f.name.clone().black().on_light_blue()])
+                            } else if marked {
+                                Line::from(vec![marker, f.name.clone().magenta().bold()])
+                            } else {
+                                Line::from(vec![
+                                    marker,
+                                    Span::styled(f.name(), Style::new().fg(f.color.into())),
+                                ])
+                            };
+
+                            if let Some(new) = self.renames.get(&f.name) {
+                                line.push_span(" → ".dark_gray());
+                                line.push_span(new.clone().green().bold());
+                            }
+
+                            line
                         }
                     })
                     .collect::<Vec<_>>(),
@@ -205,9 +380,11 @@ impl Widget for CurrDirectory {
 pub struct Finder {
     visible: bool,
     text: String,
+    status: String, // shown in the corner of the header, like how many files were searched
+    filters: String, // the active filters, shown in the other corner
     selected: usize,
     max_entries: usize,
-    files: Vec<String>,
+    files: Vec<Match>,
 }
 
 impl Finder {
@@ -215,6 +392,8 @@ impl Finder {
         self.selected = 0;
         self.files = Vec::new();
         self.text = String::new();
+        self.status = String::new();
+        self.filters = String::new();
     }
 
     pub fn text(&self) -> String {
@@ -225,7 +404,15 @@ impl Finder {
         self.text = text
     }
 
-    pub fn update_files(&mut self, files: Vec<String>) {
+    pub fn set_status(&mut self, status: String) {
+        self.status = status
+    }
+
+    pub fn set_filters(&mut self, filters: String) {
+        self.filters = filters
+    }
+
+    pub fn update_files(&mut self, files: Vec<Match>) {
         self.files = files;
         self.files.truncate(self.max_entries);
 
@@ -244,8 +431,12 @@ impl Finder {
         }
     }
 
-    pub fn selection(&self) -> &String {
-        &self.files[self.selected]
+    pub fn selection(&self) -> Option<&String> {
+        self.files.get(self.selected).map(Match::text)
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
     }
 
     pub fn max_entries(&self) -> usize {
@@ -266,18 +457,38 @@ impl Widget for Finder {
         let content_area = Rect::new(area.x, area.y + 3, area.width, area.height - 3);
 
         let header_text = Text::from(self.text);
-        let header_block = Block::bordered();
+        let status = Title::from(format!(" {} ", self.status))
+            .position(Position::Top)
+            .alignment(Alignment::Right);
+        let mut header_block = Block::bordered().title(status);
+        if !self.filters.is_empty() {
+            header_block = header_block.title(format!(" {} ", self.filters).yellow());
+        }
 
         let text = Text::from(
             self.files
                 .iter()
                 .enumerate()
-                .map(|(i, s)| {
-                    if i == self.selected {
-                        Line::from(s.as_str().black().on_white())
-                    } else {
-                        Line::from(s.as_str())
-                    }
+                .map(|(i, m)| {
+                    let selected = i == self.selected;
+                    let positions = m.positions().iter().collect::<HashSet<_>>();
+
+                    // Matched characters are highlighted
+                    Line::from(
+                        m.text()
+                            .chars()
+                            .enumerate()
+                            .map(|(j, c)| {
+                                let span = Span::raw(c.to_string());
+                                match (selected, positions.contains(&j)) {
+                                    (true, true) => span.red().bold().on_white(),
+                                    (true, false) => span.black().on_white(),
+                                    (false, true) => span.yellow().bold(),
+                                    (false, false) => span,
+                                }
+                            })
+                            .collect::<Vec<_>>(),
+                    )
                 })
                 .collect::<Vec<_>>(),
         );
@@ -353,6 +564,10 @@ impl Clipboard {
         self.files.clear();
     }
 
+    pub fn remove(&mut self, file: &Path) {
+        self.files.retain(|ce| ce.file != file);
+    }
+
     pub fn get_files(&self) -> &Vec<ClipboardEntry> {
         &self.files
     }
@@ -383,12 +598,14 @@ impl Widget for Clipboard {
 #[derive(Clone)]
 pub struct Preview {
     visible: bool,
-    lines: Vec<String>,
+    lines: Vec<Line<'static>>,
     max_lines: usize,
+    width: usize, // inside the borders
+    page: usize,
 }
 
 impl Preview {
-    pub fn update_lines(&mut self, lines: Vec<String>) {
+    pub fn update_lines(&mut self, lines: Vec<Line<'static>>) {
         self.lines = lines
     }
 
@@ -399,6 +616,30 @@ impl Preview {
     pub fn set_max_lines(&mut self, max_lines: usize) {
         self.max_lines = max_lines
     }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn scroll_page(&mut self, down: bool) {
+        if down {
+            self.page += 1;
+        } else {
+            self.page = self.page.saturating_sub(1);
+        }
+    }
+
+    pub fn reset_page(&mut self) {
+        self.page = 0;
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width
+    }
 }
 
 impl Widget for Preview {
@@ -406,13 +647,13 @@ impl Widget for Preview {
     where
         Self: Sized,
     {
-        let title = Title::from("Preview");
-        let text = Text::from(
-            self.lines
-                .iter()
-                .map(|s| Line::from(s[..s.len().min((area.width - BLOCK_LINES) as usize)].italic()))
-                .collect::<Vec<_>>(),
-        );
+        let title = if self.page > 0 {
+            Title::from(format!("Preview (page {})", self.page + 1))
+        } else {
+            Title::from("Preview")
+        };
+        // Lines too long for the pane are cut off by the paragraph
+        let text = Text::from(self.lines);
         let block = Block::bordered().title(title);
 
         Paragraph::new(text).block(block).render(area, buf);
@@ -423,6 +664,7 @@ impl Widget for Preview {
 pub struct Omnibar {
     visible: bool,
     text: String,
+    cursor: usize, // in chars, text is inserted before this position
     mode: OmnibarType,
 }
 
@@ -431,9 +673,44 @@ impl Omnibar {
         &self.text
     }
 
+    /// Replaces the text, placing the cursor at the end
     pub fn set_text(&mut self, text: String) {
+        self.cursor = text.chars().count();
         self.text = text;
     }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.chars().count());
+    }
+
+    pub fn move_cursor(&mut self, right: bool) {
+        if right {
+            self.set_cursor(self.cursor + 1);
+        } else {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let i = self.byte_index(self.cursor);
+        self.text.insert(i, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let i = self.byte_index(self.cursor);
+            self.text.remove(i);
+        }
+    }
 }
 
 impl Widget for Omnibar {
@@ -445,14 +722,276 @@ impl Widget for Omnibar {
             OmnibarType::Rename => "Rename",
             OmnibarType::Touch => "New File",
             OmnibarType::Mkdir => "New Directory",
+            OmnibarType::MarkGlob => "Mark Glob",
+            OmnibarType::PatternRename => "Pattern Rename",
+            OmnibarType::Compress => "Compress (.zip or .tar.gz)",
         });
-        let text = Text::from(Line::from(self.text));
+
+        let i = self.byte_index(self.cursor);
+        let (before, rest) = self.text.split_at(i);
+        let mut chars = rest.chars();
+        let under_cursor = chars.next().map_or(" ".to_string(), String::from);
+
+        let text = Text::from(Line::from(vec![
+            Span::raw(before.to_string()),
+            under_cursor.reversed(),
+            Span::raw(chars.collect::<String>()),
+        ]));
+        let block = Block::bordered().title(title);
+
+        Paragraph::new(text).block(block).render(area, buf);
+    }
+}
+
+#[derive(Clone)]
+pub struct OpenWith {
+    visible: bool,
+    text: String,
+    selected: usize,
+    openers: Vec<String>,
+}
+
+impl OpenWith {
+    pub fn reset(&mut self) {
+        self.selected = 0;
+        self.openers = Vec::new();
+        self.text = String::new();
+    }
+
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+
+    pub fn set_text(&mut self, text: String) {
+        self.text = text
+    }
+
+    pub fn update_openers(&mut self, openers: Vec<String>) {
+        self.openers = openers;
+        self.selected = 0;
+    }
+
+    pub fn scroll(&mut self, down: bool) {
+        if down {
+            if self.selected + 1 < self.openers.len() {
+                self.selected += 1;
+            }
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+impl Widget for OpenWith {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let header_area = Rect::new(area.x, area.y, area.width, 3);
+        let content_area = Rect::new(area.x, area.y + 3, area.width, area.height - 3);
+
+        let header_text = Text::from(self.text);
+        let header_block = Block::bordered().title(Title::from("Open With"));
+
+        let text = Text::from(
+            self.openers
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    if i == self.selected {
+                        Line::from(s.as_str().black().on_white())
+                    } else {
+                        Line::from(s.as_str())
+                    }
+                })
+                .collect::<Vec<_>>(),
+        );
+        let block = Block::bordered();
+
+        Clear.render(area, buf);
+        Paragraph::new(header_text)
+            .block(header_block)
+            .render(header_area, buf);
+        Paragraph::new(text).block(block).render(content_area, buf);
+    }
+}
+
+#[derive(Clone)]
+pub struct Conflict {
+    visible: bool,
+    lines: Vec<String>,
+}
+
+impl Conflict {
+    pub fn update_lines(&mut self, lines: Vec<String>) {
+        self.lines = lines
+    }
+}
+
+impl Widget for Conflict {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let title = Title::from("File Exists");
+        let text = Text::from(self.lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let block = Block::bordered().title(title).yellow();
+
+        Clear.render(area, buf);
+        Paragraph::new(text).block(block).render(area, buf);
+    }
+}
+
+/// A yes/no question, with `lines` giving the details of what's being confirmed
+#[derive(Clone)]
+pub struct Confirm {
+    visible: bool,
+    title: String,
+    lines: Vec<String>,
+    hint: String,
+}
+
+impl Confirm {
+    pub fn ask(&mut self, title: String, lines: Vec<String>, hint: String) {
+        self.title = title;
+        self.lines = lines;
+        self.hint = hint;
+    }
+}
+
+impl Widget for Confirm {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let title = Title::from(self.title);
+        let hint = Title::from(format!(" {} ", self.hint))
+            .position(Position::Bottom)
+            .alignment(Alignment::Right);
+        let text = Text::from(self.lines.into_iter().map(Line::from).collect::<Vec<_>>());
+        let block = Block::bordered().title(title).title(hint).red();
+
+        Clear.render(area, buf);
+        Paragraph::new(text).block(block).render(area, buf);
+    }
+}
+
+#[derive(Clone)]
+pub struct TrashList {
+    visible: bool,
+    entries: Vec<(String, String)>, // (deletion date, original path)
+    scroll: usize,
+    selected: usize, // index into entries, not relative to scroll
+    max_entries: usize,
+}
+
+impl TrashList {
+    pub fn update_entries(&mut self, entries: Vec<(String, String)>) {
+        self.entries = entries;
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        self.scroll = self.scroll.min(self.selected);
+    }
+
+    pub fn scroll(&mut self, down: bool) {
+        if down {
+            if self.selected + 1 < self.entries.len() {
+                self.selected += 1;
+            }
+        } else {
+            self.selected = self.selected.saturating_sub(1);
+        }
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.max_entries != 0 && self.selected >= self.scroll + self.max_entries {
+            self.scroll = self.selected + 1 - self.max_entries;
+        }
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        if self.entries.is_empty() {
+            None
+        } else {
+            Some(self.selected)
+        }
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries
+    }
+}
+
+impl Widget for TrashList {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let title = Title::from(format!("Trash ({} items)", self.entries.len()));
+        let text = Text::from(
+            self.entries
+                .iter()
+                .enumerate()
+                .skip(self.scroll)
+                .map(|(i, (date, path))| {
+                    let date = date.replace('T', " ");
+                    if i == self.selected {
+                        Line::from(vec![
+                            format!("{}  ", date).dark_gray(),
+                            path.clone().black().on_white(),
+                        ])
+                    } else {
+                        Line::from(vec![format!("{}  ", date).dark_gray(), path.clone().into()])
+                    }
+                })
+                .collect::<Vec<_>>(),
+        );
         let block = Block::bordered().title(title);
 
         Paragraph::new(text).block(block).render(area, buf);
     }
 }
 
+#[derive(Clone)]
+pub struct Status {
+    message: Option<(String, bool)>, // (message, true if it's an error)
+}
+
+impl Status {
+    pub fn info(&mut self, message: String) {
+        self.message = Some((message, false));
+    }
+
+    pub fn error(&mut self, message: String) {
+        self.message = Some((message, true));
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+    }
+}
+
+impl Widget for Status {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if let Some((message, error)) = self.message {
+            let span = format!(" {} ", message);
+            let line = if error {
+                Line::from(span.white().on_red())
+            } else {
+                Line::from(span.black().on_green())
+            };
+
+            Paragraph::new(line).render(area, buf);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Window {
     pub(crate) file_list: FileList,
@@ -461,6 +1000,11 @@ pub struct Window {
     pub(crate) clipboard: Clipboard,
     pub(crate) preview: Preview,
     pub(crate) omnibar: Omnibar,
+    pub(crate) open_with: OpenWith,
+    pub(crate) conflict: Conflict,
+    pub(crate) trash: TrashList,
+    pub(crate) confirm: Confirm,
+    pub(crate) status: Status,
 }
 
 impl Window {
@@ -472,6 +1016,9 @@ impl Window {
             max_entries: 0,
             hint_mode: false,
             hint_choices: FileList::initialize_hints(),
+            marked: HashSet::new(),
+            anchor: None,
+            renames: HashMap::new(),
             visible: true,
         };
 
@@ -485,6 +1032,8 @@ impl Window {
             selected: 0,
             max_entries: 0,
             text: String::new(),
+            status: String::new(),
+            filters: String::new(),
             files: Vec::new(),
         };
 
@@ -498,14 +1047,46 @@ impl Window {
             visible: true,
             lines: Vec::new(),
             max_lines: 0,
+            width: 0,
+            page: 0,
         };
 
         let omnibar = Omnibar {
             visible: false,
             text: String::new(),
+            cursor: 0,
             mode: OmnibarType::Rename,
         };
 
+        let open_with = OpenWith {
+            visible: false,
+            text: String::new(),
+            selected: 0,
+            openers: Vec::new(),
+        };
+
+        let conflict = Conflict {
+            visible: false,
+            lines: Vec::new(),
+        };
+
+        let trash = TrashList {
+            visible: false,
+            entries: Vec::new(),
+            scroll: 0,
+            selected: 0,
+            max_entries: 0,
+        };
+
+        let confirm = Confirm {
+            visible: false,
+            title: String::new(),
+            lines: Vec::new(),
+            hint: String::new(),
+        };
+
+        let status = Status { message: None };
+
         Self {
             file_list,
             curr_dir,
@@ -513,6 +1094,11 @@ impl Window {
             clipboard,
             preview,
             omnibar,
+            open_with,
+            conflict,
+            trash,
+            confirm,
+            status,
         }
     }
 
@@ -520,12 +1106,13 @@ impl Window {
         self.file_list.hint_mode(on);
     }
 
-    pub fn finder_mode(&mut self, on: bool) {
+    pub fn finder_mode(&mut self, on: bool, kind: FinderType) {
         if on {
             self.file_list.visible = false;
             self.curr_dir.visible = false;
             self.clipboard.visible = false;
-            self.preview.visible = false;
+            // Content search results are previewed at the matching line
+            self.preview.visible = kind == FinderType::Grep;
             self.omnibar.visible = false;
             self.finder.visible = true;
         } else {
@@ -539,7 +1126,13 @@ impl Window {
 
     pub fn omnibar_mode(&mut self, on: bool, mode: OmnibarType) {
         self.omnibar.mode = mode;
-        if on {
+
+        // Pattern renames are previewed in the file list, so the omnibar takes the place of the
+        // current directory instead of covering everything
+        if let OmnibarType::PatternRename = mode {
+            self.curr_dir.visible = !on;
+            self.omnibar.visible = on;
+        } else if on {
             self.file_list.visible = false;
             self.curr_dir.visible = false;
             self.clipboard.visible = false;
@@ -554,6 +1147,26 @@ impl Window {
             self.omnibar.visible = false;
         }
     }
+
+    pub fn open_with_mode(&mut self, on: bool) {
+        self.open_with.visible = on;
+    }
+
+    pub fn trash_mode(&mut self, on: bool) {
+        self.file_list.visible = !on;
+        self.curr_dir.visible = !on;
+        self.clipboard.visible = !on;
+        self.preview.visible = !on;
+        self.trash.visible = on;
+    }
+
+    pub fn confirm_mode(&mut self, on: bool) {
+        self.confirm.visible = on;
+    }
+
+    pub fn conflict_mode(&mut self, on: bool) {
+        self.conflict.visible = on;
+    }
 }
 
 impl Widget for Window {
@@ -581,7 +1194,12 @@ impl Widget for Window {
             area.height / 3,
         );
 
-        let fd_area = Rect::new(area.width / 8, 0, 3 * area.width / 4, area.height);
+        // Takes the place of the file list when the preview is shown next to it
+        let fd_area = if self.preview.visible {
+            Rect::new(0, 0, 3 * area.width / 4, area.height)
+        } else {
+            Rect::new(area.width / 8, 0, 3 * area.width / 4, area.height)
+        };
 
         let rn_area = Rect::new(
             area.width / 4,
@@ -590,6 +1208,20 @@ impl Widget for Window {
             RENAME_HEIGHT,
         );
 
+        let st_area = Rect::new(
+            1,
+            area.height.saturating_sub(1),
+            (3 * area.width / 4).saturating_sub(2),
+            1,
+        );
+
+        let ow_area = Rect::new(
+            area.width / 4,
+            area.height / 4,
+            area.width / 2,
+            area.height / 2,
+        );
+
         if self.file_list.visible {
             self.file_list.render(fl_area, buf);
         }
@@ -611,7 +1243,30 @@ impl Widget for Window {
         }
 
         if self.omnibar.visible {
-            self.omnibar.render(rn_area, buf)
+            if let OmnibarType::PatternRename = self.omnibar.mode {
+                self.omnibar.render(cd_area, buf)
+            } else {
+                self.omnibar.render(rn_area, buf)
+            }
+        }
+
+        if self.trash.visible {
+            self.trash.render(fd_area, buf)
+        }
+
+        if self.open_with.visible {
+            self.open_with.render(ow_area, buf)
         }
+
+        if self.conflict.visible {
+            self.conflict.render(ow_area, buf)
+        }
+
+        if self.confirm.visible {
+            self.confirm.render(ow_area, buf)
+        }
+
+        // Drawn over the bottom border of the file list
+        self.status.render(st_area, buf);
     }
 }
@@ -1,6 +1,12 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fs, path::Path};
 
-use crate::Result;
+use crate::{
+    associations::{is_text_file, Associations},
+    opener::Opener,
+    preview::Previewer,
+    search::Filters,
+    Result,
+};
 
 use homedir::my_home;
 use ratatui::crossterm::event::KeyCode;
@@ -11,6 +17,16 @@ pub enum OmnibarType {
     Rename,
     Touch,
     Mkdir,
+    MarkGlob,
+    PatternRename,
+    Compress,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinderType {
+    Files,
+    Jump, // recently used directories
+    Grep, // file contents
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -18,11 +34,23 @@ pub enum FileListCommand {
     EntryScroll(bool), // true if down scroll
     SelectEntry,       // Selected entry (doesn't distinguish between dirs/files)
     HintMode,
-    FinderMode(bool), // true if zoxide search
+    VisualMode,
+    FinderMode(FinderType),
     OmnibarMode(OmnibarType),
+    OpenWith,
+    BulkRename,
     Yank(bool), // true if cut
     Paste,
-    Delete(bool), // true if force (meaning it can delete directories)
+    Delete(bool), // true if force (meaning it's removed permanently instead of trashed)
+    Extract,
+    TrashMode,
+    Undo,
+    Redo,
+    ToggleMark,
+    MarkAll,
+    InvertMarks,
+    ClearMarks,
+    PreviewScroll(bool), // true if down, by a page
 
     Exit,
     ExitHint,
@@ -34,6 +62,25 @@ impl FileListCommand {
     pub fn should_refresh_preview(&self) -> bool {
         !matches!(self, FileListCommand::Exit | FileListCommand::None)
     }
+
+    /// Returns true if the command changes the contents of the current directory, which can't be
+    /// done inside an archive
+    pub fn modifies_dir(&self) -> bool {
+        matches!(
+            self,
+            FileListCommand::OmnibarMode(
+                OmnibarType::Rename
+                    | OmnibarType::Touch
+                    | OmnibarType::Mkdir
+                    | OmnibarType::PatternRename
+                    | OmnibarType::Compress
+            ) | FileListCommand::BulkRename
+                | FileListCommand::Yank(true)
+                | FileListCommand::Paste
+                | FileListCommand::Delete(_)
+                | FileListCommand::Extract
+        )
+    }
 }
 
 impl From<&str> for FileListCommand {
@@ -43,14 +90,33 @@ impl From<&str> for FileListCommand {
             "scroll_up" => FileListCommand::EntryScroll(false),
             "select_entry" => FileListCommand::SelectEntry,
             "hint_mode" => FileListCommand::HintMode,
-            "finder_fzf" => FileListCommand::FinderMode(false),
-            "finder_zoxide" => FileListCommand::FinderMode(true),
+            "visual_mode" => FileListCommand::VisualMode,
+            "finder_fzf" => FileListCommand::FinderMode(FinderType::Files),
+            "finder_zoxide" => FileListCommand::FinderMode(FinderType::Jump),
+            "finder_grep" => FileListCommand::FinderMode(FinderType::Grep),
             "rename" => FileListCommand::OmnibarMode(OmnibarType::Rename),
             "touch" => FileListCommand::OmnibarMode(OmnibarType::Touch),
             "mkdir" => FileListCommand::OmnibarMode(OmnibarType::Mkdir),
+            "open_with" => FileListCommand::OpenWith,
+            "bulk_rename" => FileListCommand::BulkRename,
+            "pattern_rename" => FileListCommand::OmnibarMode(OmnibarType::PatternRename),
             "yank" => FileListCommand::Yank(false),
             "cut" => FileListCommand::Yank(true),
             "paste" => FileListCommand::Paste,
+            "delete" => FileListCommand::Delete(false),
+            "force_delete" => FileListCommand::Delete(true),
+            "compress" => FileListCommand::OmnibarMode(OmnibarType::Compress),
+            "extract" => FileListCommand::Extract,
+            "trash_mode" => FileListCommand::TrashMode,
+            "undo" => FileListCommand::Undo,
+            "redo" => FileListCommand::Redo,
+            "toggle_mark" => FileListCommand::ToggleMark,
+            "mark_all" => FileListCommand::MarkAll,
+            "invert_marks" => FileListCommand::InvertMarks,
+            "clear_marks" => FileListCommand::ClearMarks,
+            "mark_glob" => FileListCommand::OmnibarMode(OmnibarType::MarkGlob),
+            "preview_down" => FileListCommand::PreviewScroll(true),
+            "preview_up" => FileListCommand::PreviewScroll(false),
             "exit" => FileListCommand::Exit,
             "exit_hint" => FileListCommand::ExitHint,
             _ => FileListCommand::None,
@@ -58,12 +124,24 @@ impl From<&str> for FileListCommand {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum FinderFilter {
+    FilesOnly,
+    DirsOnly,
+    Hidden,
+    GitIgnore,
+    FollowLinks,
+    MaxDepth,
+    FileType,
+}
+
 #[derive(Clone, Copy)]
 pub enum FinderCommand {
     Write(char),
     Backspace,
     SelectEntry,
     EntryScroll(bool),
+    ToggleFilter(FinderFilter),
 
     Exit,
 
@@ -77,6 +155,13 @@ impl From<&str> for FinderCommand {
             "select_entry" => FinderCommand::SelectEntry,
             "scroll_down" => FinderCommand::EntryScroll(true),
             "scroll_up" => FinderCommand::EntryScroll(false),
+            "toggle_files_only" => FinderCommand::ToggleFilter(FinderFilter::FilesOnly),
+            "toggle_dirs_only" => FinderCommand::ToggleFilter(FinderFilter::DirsOnly),
+            "toggle_hidden" => FinderCommand::ToggleFilter(FinderFilter::Hidden),
+            "toggle_git_ignore" => FinderCommand::ToggleFilter(FinderFilter::GitIgnore),
+            "toggle_follow_links" => FinderCommand::ToggleFilter(FinderFilter::FollowLinks),
+            "cycle_max_depth" => FinderCommand::ToggleFilter(FinderFilter::MaxDepth),
+            "cycle_type" => FinderCommand::ToggleFilter(FinderFilter::FileType),
             "exit" => FinderCommand::Exit,
             _ => FinderCommand::None,
         }
@@ -87,6 +172,7 @@ impl From<&str> for FinderCommand {
 pub enum OmnibarCommand {
     Write(char),
     Backspace,
+    CursorMove(bool), // true if moving right
 
     Submit,
 
@@ -99,6 +185,8 @@ impl From<&str> for OmnibarCommand {
     fn from(value: &str) -> Self {
         match value {
             "backspace" => OmnibarCommand::Backspace,
+            "cursor_left" => OmnibarCommand::CursorMove(false),
+            "cursor_right" => OmnibarCommand::CursorMove(true),
             "submit" => OmnibarCommand::Submit,
             "exit" => OmnibarCommand::Exit,
             _ => OmnibarCommand::None,
@@ -106,10 +194,158 @@ impl From<&str> for OmnibarCommand {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictCommand {
+    Overwrite(bool), // true if the choice applies to every remaining conflict
+    Skip(bool),
+    Rename(bool),
+
+    Exit,
+
+    None,
+}
+
+impl ConflictCommand {
+    pub fn applies_to_all(&self) -> bool {
+        matches!(
+            self,
+            ConflictCommand::Overwrite(true)
+                | ConflictCommand::Skip(true)
+                | ConflictCommand::Rename(true)
+        )
+    }
+
+    fn order(&self) -> usize {
+        match self {
+            ConflictCommand::Overwrite(false) => 0,
+            ConflictCommand::Skip(false) => 1,
+            ConflictCommand::Rename(false) => 2,
+            ConflictCommand::Overwrite(true) => 3,
+            ConflictCommand::Skip(true) => 4,
+            ConflictCommand::Rename(true) => 5,
+            ConflictCommand::Exit => 6,
+            ConflictCommand::None => 7,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ConflictCommand::Overwrite(false) => "overwrite",
+            ConflictCommand::Skip(false) => "skip",
+            ConflictCommand::Rename(false) => "rename",
+            ConflictCommand::Overwrite(true) => "overwrite all",
+            ConflictCommand::Skip(true) => "skip all",
+            ConflictCommand::Rename(true) => "rename all",
+            ConflictCommand::Exit => "cancel paste",
+            ConflictCommand::None => "",
+        }
+    }
+}
+
+impl From<&str> for ConflictCommand {
+    fn from(value: &str) -> Self {
+        match value {
+            "overwrite" => ConflictCommand::Overwrite(false),
+            "skip" => ConflictCommand::Skip(false),
+            "rename" => ConflictCommand::Rename(false),
+            "overwrite_all" => ConflictCommand::Overwrite(true),
+            "skip_all" => ConflictCommand::Skip(true),
+            "rename_all" => ConflictCommand::Rename(true),
+            "exit" => ConflictCommand::Exit,
+            _ => ConflictCommand::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum TrashCommand {
+    EntryScroll(bool),
+    Restore,
+    Purge,
+
+    Exit,
+
+    None,
+}
+
+impl From<&str> for TrashCommand {
+    fn from(value: &str) -> Self {
+        match value {
+            "scroll_down" => TrashCommand::EntryScroll(true),
+            "scroll_up" => TrashCommand::EntryScroll(false),
+            "restore" => TrashCommand::Restore,
+            "purge" => TrashCommand::Purge,
+            "exit" => TrashCommand::Exit,
+            _ => TrashCommand::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ConfirmCommand {
+    Yes,
+    No,
+
+    None,
+}
+
+impl From<&str> for ConfirmCommand {
+    fn from(value: &str) -> Self {
+        match value {
+            "yes" => ConfirmCommand::Yes,
+            "no" | "exit" => ConfirmCommand::No,
+            _ => ConfirmCommand::None,
+        }
+    }
+}
+
+/// What the file finder filters its results with
+#[derive(Clone, Copy)]
+pub enum FinderBackend {
+    Native,
+    Fzf,
+}
+
+impl From<&str> for FinderBackend {
+    fn from(value: &str) -> Self {
+        match value {
+            "native" => FinderBackend::Native,
+            "fzf" => FinderBackend::Fzf,
+            b => panic!("Unknown search backend {}", b),
+        }
+    }
+}
+
+/// Where the jump finder gets its directories from
+#[derive(Clone, Copy)]
+pub enum JumpBackend {
+    Native,
+    Zoxide,
+}
+
+impl From<&str> for JumpBackend {
+    fn from(value: &str) -> Self {
+        match value {
+            "native" => JumpBackend::Native,
+            "zoxide" => JumpBackend::Zoxide,
+            b => panic!("Unknown jump backend {}", b),
+        }
+    }
+}
+
 pub struct Config {
     file_list_bindings: HashMap<KeyCode, FileListCommand>,
     finder_bindings: HashMap<KeyCode, FinderCommand>,
     omnibar_bindings: HashMap<KeyCode, OmnibarCommand>,
+    conflict_bindings: HashMap<KeyCode, ConflictCommand>,
+    trash_bindings: HashMap<KeyCode, TrashCommand>,
+    confirm_bindings: HashMap<KeyCode, ConfirmCommand>,
+    openers: Associations<Opener>,
+    finder_backend: FinderBackend,
+    finder_filters: Filters,
+    jump_backend: JumpBackend,
+    import_zoxide: bool,
+    previewers: Associations<Previewer>,
 }
 
 impl Config {
@@ -132,153 +368,237 @@ impl Config {
             file_list_bindings: Self::init_file_list(&user_table, &default_table)?,
             finder_bindings: Self::init_finder(&user_table, &default_table)?,
             omnibar_bindings: Self::init_omnibar(&user_table, &default_table)?,
+            conflict_bindings: Self::init_conflict(&user_table, &default_table)?,
+            trash_bindings: Self::init_trash(&user_table, &default_table)?,
+            confirm_bindings: Self::init_confirm(&user_table, &default_table)?,
+            openers: Associations::from_tables(
+                "openers",
+                &user_table,
+                &default_table,
+                Opener::from_value,
+            ),
+            finder_backend: Self::setting("search", "backend", &user_table, &default_table)
+                .as_str()
+                .expect("search.backend should be a string")
+                .into(),
+            finder_filters: Self::init_filters(&user_table, &default_table),
+            jump_backend: Self::setting("search", "jump_backend", &user_table, &default_table)
+                .as_str()
+                .expect("search.jump_backend should be a string")
+                .into(),
+            import_zoxide: Self::setting("search", "import_zoxide", &user_table, &default_table)
+                .as_bool()
+                .expect("search.import_zoxide should be true or false"),
+            previewers: Associations::from_tables(
+                "preview",
+                &user_table,
+                &default_table,
+                Previewer::from_value,
+            ),
         })
     }
 
-    fn init_file_list(
+    // Reads a single setting from `section`, preferring the user's config over the defaults
+    fn setting<'a>(
+        section: &str,
+        key: &str,
+        user_table: &'a Table,
+        default_table: &'a Table,
+    ) -> &'a Value {
+        user_table
+            .get(section)
+            .and_then(|s| s.get(key))
+            .or_else(|| default_table.get(section).and_then(|s| s.get(key)))
+            .unwrap_or_else(|| panic!("{}.{} missing from default config", section, key))
+    }
+
+    // Reads the bindings for `keys` from `section`, preferring the user's config over the defaults
+    fn init_bindings<T: for<'a> From<&'a str>>(
+        section: &str,
+        keys: &[&str],
+        allow_char: bool, // false in modes where char keys are used for text input
         user_table: &Table,
         default_table: &Table,
-    ) -> Result<HashMap<KeyCode, FileListCommand>> {
-        let user_bindings = if let Some(Value::Table(t)) = user_table.get("filelist") {
+    ) -> Result<HashMap<KeyCode, T>> {
+        let user_bindings = if let Some(Value::Table(t)) = user_table.get(section) {
             t
         } else {
-           &Table::new()
+            &Table::new()
         };
 
         let default_bindings = default_table
-            .get("filelist")
+            .get(section)
             .expect("Unable to parse default config")
             .as_table()
-            .expect("filelist section in default config is corrupted");
+            .unwrap_or_else(|| panic!("{} section in default config is corrupted", section));
 
         let mut map = HashMap::new();
 
+        for k in keys {
+            let str = if let Some(Value::String(s)) = user_bindings.get(*k) {
+                if !allow_char && s.len() <= 1 {
+                    panic!("Can't assign char key in {} mode", section)
+                } else {
+                    s
+                }
+            } else {
+                default_bindings
+                    .get(*k)
+                    .expect("Unable to parse default config")
+                    .as_str()
+                    .expect("Unable to parse default config")
+            };
+
+            let code = keycode_from_str(str);
+
+            map.insert(code, (*k).into());
+        }
+
+        Ok(map)
+    }
+
+    fn init_file_list(
+        user_table: &Table,
+        default_table: &Table,
+    ) -> Result<HashMap<KeyCode, FileListCommand>> {
         let keys = [
             "scroll_down",
             "scroll_up",
             "select_entry",
             "hint_mode",
+            "visual_mode",
             "finder_fzf",
             "finder_zoxide",
+            "finder_grep",
             "rename",
             "touch",
             "mkdir",
+            "open_with",
+            "bulk_rename",
+            "pattern_rename",
             "yank",
             "cut",
             "paste",
+            "delete",
+            "force_delete",
+            "compress",
+            "extract",
+            "trash_mode",
+            "undo",
+            "redo",
+            "toggle_mark",
+            "mark_all",
+            "invert_marks",
+            "clear_marks",
+            "mark_glob",
+            "preview_down",
+            "preview_up",
             "exit",
-            "exit_hint"
+            "exit_hint",
         ];
 
-        for k in keys {
-            let str = if let Some(Value::String(s)) = user_bindings.get(k) {
-                s
-            } else {
-                default_bindings
-                    .get(k)
-                    .expect("Unable to parse default config")
-                    .as_str()
-                    .expect("Unable to parse default config")
-            };
-
-            let code = keycode_from_str(str);
-
-            map.insert(code, k.into());
-        }
-        
-        Ok(map)
+        Self::init_bindings("filelist", &keys, true, user_table, default_table)
     }
 
     fn init_finder(
         user_table: &Table,
         default_table: &Table,
     ) -> Result<HashMap<KeyCode, FinderCommand>> {
-        let user_bindings = if let Some(Value::Table(t)) = user_table.get("finder") {
-            t
-        } else {
-            &Table::new()
-        };
-
-        let default_bindings = default_table
-            .get("finder")
-            .expect("Unable to parse default config")
-            .as_table()
-            .expect("filelist section in default config is corrupted");
-
-        let mut map = HashMap::new();
-
         let keys = [
             "backspace",
             "select_entry",
             "scroll_down",
             "scroll_up",
+            "toggle_files_only",
+            "toggle_dirs_only",
+            "toggle_hidden",
+            "toggle_git_ignore",
+            "toggle_follow_links",
+            "cycle_max_depth",
+            "cycle_type",
             "exit",
         ];
 
-        for k in keys {
-            let str = if let Some(Value::String(s)) = user_bindings.get(k) {
-                if s.len() <= 1 {
-                    panic!("Can't assign char key in finder mode")
-                } else {
-                    s
-                }
-            } else {
-                default_bindings
-                    .get(k)
-                    .expect("Unable to parse default config")
-                    .as_str()
-                    .expect("Unable to parse default config")
-            };
-
-            let code = keycode_from_str(str);
+        Self::init_bindings("finder", &keys, false, user_table, default_table)
+    }
 
-            map.insert(code, k.into());
+    fn init_filters(user_table: &Table, default_table: &Table) -> Filters {
+        let flag = |key: &str| {
+            Self::setting("search", key, user_table, default_table)
+                .as_bool()
+                .unwrap_or_else(|| panic!("search.{} should be true or false", key))
+        };
+        let max_depth = Self::setting("search", "max_depth", user_table, default_table)
+            .as_integer()
+            .expect("search.max_depth should be a number");
+        let file_type = Self::setting("search", "type", user_table, default_table)
+            .as_str()
+            .expect("search.type should be a string");
+        let types = Self::setting("search", "types", user_table, default_table)
+            .as_array()
+            .expect("search.types should be a list of file types")
+            .iter()
+            .map(|t| {
+                t.as_str()
+                    .expect("search.types should be a list of file types")
+                    .to_string()
+            })
+            .collect();
+
+        Filters {
+            files_only: flag("files_only"),
+            dirs_only: flag("dirs_only"),
+            hidden: flag("hidden"),
+            git_ignore: flag("git_ignore"),
+            follow_links: flag("follow_links"),
+            max_depth: (max_depth > 0).then_some(max_depth as usize),
+            file_type: (!file_type.is_empty()).then(|| file_type.to_string()),
+            types,
         }
-
-        Ok(map)
     }
 
     fn init_omnibar(
         user_table: &Table,
         default_table: &Table,
     ) -> Result<HashMap<KeyCode, OmnibarCommand>> {
-        let user_bindings = if let Some(Value::Table(t)) = user_table.get("omnibar") {
-            t
-        } else {
-            &Table::new()
-        };
+        let keys = ["backspace", "cursor_left", "cursor_right", "submit", "exit"];
 
-        let default_bindings = default_table
-            .get("omnibar")
-            .expect("Unable to parse default config")
-            .as_table()
-            .expect("filelist section in default config is corrupted");
+        Self::init_bindings("omnibar", &keys, false, user_table, default_table)
+    }
 
-        let mut map = HashMap::new();
+    fn init_conflict(
+        user_table: &Table,
+        default_table: &Table,
+    ) -> Result<HashMap<KeyCode, ConflictCommand>> {
+        let keys = [
+            "overwrite",
+            "skip",
+            "rename",
+            "overwrite_all",
+            "skip_all",
+            "rename_all",
+            "exit",
+        ];
 
-        let keys = ["backspace", "submit", "exit"];
+        Self::init_bindings("conflict", &keys, true, user_table, default_table)
+    }
 
-        for k in keys {
-            let str = if let Some(Value::String(s)) = user_bindings.get(k) {
-                if s.len() <= 1 {
-                    panic!("Can't assign char key in omnibar mode")
-                } else {
-                    s
-                }
-            } else {
-                default_bindings
-                    .get(k)
-                    .expect("Unable to parse default config")
-                    .as_str()
-                    .expect("Unable to parse default config")
-            };
+    fn init_trash(
+        user_table: &Table,
+        default_table: &Table,
+    ) -> Result<HashMap<KeyCode, TrashCommand>> {
+        let keys = ["scroll_down", "scroll_up", "restore", "purge", "exit"];
 
-            let code = keycode_from_str(str);
+        Self::init_bindings("trash", &keys, true, user_table, default_table)
+    }
 
-            map.insert(code, k.into());
-        }
+    fn init_confirm(
+        user_table: &Table,
+        default_table: &Table,
+    ) -> Result<HashMap<KeyCode, ConfirmCommand>> {
+        let keys = ["yes", "no", "exit"];
 
-        Ok(map)
+        Self::init_bindings("confirm", &keys, true, user_table, default_table)
     }
 
     pub fn get_filelist_command(&self, code: KeyCode) -> Option<FileListCommand> {
@@ -300,6 +620,102 @@ impl Config {
             self.omnibar_bindings.get(&code).copied()
         }
     }
+
+    pub fn get_conflict_command(&self, code: KeyCode) -> Option<ConflictCommand> {
+        self.conflict_bindings.get(&code).copied()
+    }
+
+    pub fn get_trash_command(&self, code: KeyCode) -> Option<TrashCommand> {
+        self.trash_bindings.get(&code).copied()
+    }
+
+    pub fn get_confirm_command(&self, code: KeyCode) -> Option<ConfirmCommand> {
+        self.confirm_bindings.get(&code).copied()
+    }
+
+    /// Describes the confirm bindings, e.g. `y yes / n no`
+    pub fn describe_confirm_bindings(&self) -> String {
+        let key = |command: fn(&ConfirmCommand) -> bool| {
+            self.confirm_bindings
+                .iter()
+                .filter(|(_, c)| command(c))
+                .map(|(k, _)| k.to_string())
+                .min_by_key(|k| (k.len(), k.clone())) // prefer `n` over `Esc`
+                .unwrap_or_default()
+        };
+
+        format!(
+            "{} yes / {} no",
+            key(|c| matches!(c, ConfirmCommand::Yes)),
+            key(|c| matches!(c, ConfirmCommand::No))
+        )
+    }
+
+    /// Describes the conflict bindings, e.g. `o overwrite`, in the order they're defined in
+    pub fn describe_conflict_bindings(&self) -> Vec<String> {
+        let mut bindings = self.conflict_bindings.iter().collect::<Vec<_>>();
+        bindings.sort_by_key(|(_, c)| c.order());
+
+        bindings
+            .into_iter()
+            .map(|(k, c)| format!("{} {}", k, c.description()))
+            .collect()
+    }
+
+    pub fn finder_backend(&self) -> FinderBackend {
+        self.finder_backend
+    }
+
+    pub fn jump_backend(&self) -> JumpBackend {
+        self.jump_backend
+    }
+
+    pub fn import_zoxide(&self) -> bool {
+        self.import_zoxide
+    }
+
+    /// How `path` should be previewed. Files without a matching previewer are shown as text, and
+    /// anything else isn't previewed.
+    pub fn get_previewer(&self, path: &Path) -> Option<Previewer> {
+        self.previewers
+            .lookup(path)
+            .cloned()
+            .or_else(|| path.is_file().then_some(Previewer::Text))
+    }
+
+    /// The filters the finder starts out with
+    pub fn finder_filters(&self) -> &Filters {
+        &self.finder_filters
+    }
+
+    /// Returns the configured opener for `path`, falling back to `$VISUAL` or `$EDITOR` for text
+    /// files and `xdg-open` for anything else
+    pub fn get_opener(&self, path: &Path) -> Option<Opener> {
+        self.openers
+            .lookup(path)
+            .cloned()
+            .or_else(|| Opener::fallbacks(is_text_file(path)).into_iter().next())
+    }
+
+    /// Returns every opener that applies to `path`, starting with the configured ones
+    pub fn get_openers(&self, path: &Path) -> Vec<Opener> {
+        let mut openers: Vec<Opener> = Vec::new();
+
+        let fallbacks = Opener::fallbacks(is_text_file(path));
+        for o in self
+            .openers
+            .lookup_all(path)
+            .into_iter()
+            .cloned()
+            .chain(fallbacks)
+        {
+            if !openers.iter().any(|e| e.name() == o.name()) {
+                openers.push(o);
+            }
+        }
+
+        openers
+    }
 }
 
 pub fn keycode_from_str(s: &str) -> KeyCode {
@@ -322,7 +738,10 @@ pub fn keycode_from_str(s: &str) -> KeyCode {
             "tab" => KeyCode::Tab,
             "backtab" => KeyCode::BackTab,
             "esc" => KeyCode::Esc,
-            _ => KeyCode::Null,
+            f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n) => KeyCode::F(n),
+                None => KeyCode::Null,
+            },
         }
     }
 }
@@ -0,0 +1,182 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fuzzy::{self, Match},
+    load_toml, save_toml, xdg_dir, Result,
+};
+
+// Once the ranks add up to more than this, they're all scaled down and the least used
+// directories are forgotten, like zoxide does
+const MAX_TOTAL_RANK: f64 = 10000.0;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Serialize, Deserialize)]
+struct Dir {
+    path: PathBuf,
+    rank: f64,        // roughly how many times it's been visited
+    last_access: u64, // in seconds since the epoch
+}
+
+impl Dir {
+    // Recent visits count for more
+    fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_access);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+
+        self.rank * weight
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Database {
+    dirs: Vec<Dir>,
+}
+
+/// Remembers which directories are visited, and how often and recently, so they can be jumped
+/// back to
+pub struct Frecency {
+    path: PathBuf,
+    database: Database,
+}
+
+impl Frecency {
+    /// Loads the database from `$XDG_DATA_HOME/thunars`, which defaults to `~/.local/share`. A new
+    /// database starts out with zoxide's directories if `import_zoxide` is true.
+    pub fn init(import_zoxide: bool) -> Result<Self> {
+        let dir = xdg_dir("XDG_DATA_HOME", ".local/share")?.join("thunars");
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join("frecency.toml");
+        let exists = path.exists();
+        let database = load_toml(&path)?;
+
+        let mut frecency = Self { path, database };
+
+        // New databases are saved straight away so that zoxide is only imported once
+        if !exists {
+            if import_zoxide {
+                // Not having zoxide installed is fine, there's just nothing to import
+                let _ = frecency.import_zoxide();
+            }
+
+            frecency.save()?;
+        }
+
+        Ok(frecency)
+    }
+
+    fn save(&self) -> Result<()> {
+        save_toml(&self.path, &self.database)
+    }
+
+    /// Records a visit to `dir`
+    pub fn visit(&mut self, dir: &Path) -> Result<()> {
+        let now = now();
+
+        match self.database.dirs.iter_mut().find(|d| d.path == dir) {
+            Some(d) => {
+                d.rank += 1.0;
+                d.last_access = now;
+            }
+            None => self.database.dirs.push(Dir {
+                path: dir.to_path_buf(),
+                rank: 1.0,
+                last_access: now,
+            }),
+        }
+
+        self.age();
+        self.save()
+    }
+
+    fn age(&mut self) {
+        let total = self.database.dirs.iter().map(|d| d.rank).sum::<f64>();
+
+        if total > MAX_TOTAL_RANK {
+            let factor = 0.9 * MAX_TOTAL_RANK / total;
+            for d in &mut self.database.dirs {
+                d.rank *= factor;
+            }
+
+            self.database.dirs.retain(|d| d.rank >= 1.0);
+        }
+    }
+
+    /// Returns the directories fuzzy matching `query`, most frecent first. Directories that no
+    /// longer exist are left out.
+    pub fn query(&self, query: &str, limit: usize) -> Vec<Match> {
+        let now = now();
+
+        let mut dirs = self
+            .database
+            .dirs
+            .iter()
+            .filter(|d| d.path.is_dir())
+            .filter_map(|d| {
+                fuzzy::fuzzy_match(query, &d.path.to_string_lossy()).map(|m| (d.score(now), m))
+            })
+            .collect::<Vec<_>>();
+
+        dirs.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        dirs.into_iter().take(limit).map(|(_, m)| m).collect()
+    }
+
+    // Adds the directories in zoxide's database
+    fn import_zoxide(&mut self) -> Result<()> {
+        let output = Command::new("zoxide")
+            .args(["query", "--list", "--score"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+
+        // Lines look like `  12.5 /home/user/projects`
+        let now = now();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((score, path)) = line.trim_start().split_once(' ') else {
+                continue;
+            };
+            let Ok(score) = score.parse::<f64>() else {
+                continue;
+            };
+
+            let path = PathBuf::from(path);
+            match self.database.dirs.iter_mut().find(|d| d.path == path) {
+                Some(d) => d.rank += score,
+                None => self.database.dirs.push(Dir {
+                    path,
+                    rank: score,
+                    last_access: now,
+                }),
+            }
+        }
+
+        self.age();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    env,
+    fs::{self, DirBuilder, File, FileTimes, Metadata},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::Result;
+
+/// Copies `src` to `dst`, recursing into directories. Symlinks are recreated rather than
+/// followed, and permissions and modification times are carried over.
+pub fn copy_recursive(src: &Path, dst: &Path) -> Result<()> {
+    if dst.starts_with(src) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("Can't copy {} into itself", src.to_string_lossy()),
+        )
+        .into());
+    }
+
+    copy_entry(src, dst)
+}
+
+fn copy_entry(src: &Path, dst: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+
+    if meta.is_symlink() {
+        copy_symlink(src, dst)?;
+    } else if meta.is_dir() {
+        fs::create_dir(dst)?;
+
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+
+        // Has to happen after the children are written, since that updates the mtime
+        copy_metadata(&meta, dst)?;
+    } else {
+        fs::copy(src, dst)?;
+        copy_metadata(&meta, dst)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dst)?;
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
+    let target = fs::read_link(src)?;
+
+    if fs::metadata(src).is_ok_and(|m| m.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dst)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, dst)?;
+    }
+
+    Ok(())
+}
+
+fn copy_metadata(meta: &Metadata, dst: &Path) -> Result<()> {
+    fs::set_permissions(dst, meta.permissions())?;
+
+    if let Ok(modified) = meta.modified() {
+        // Read-only handles are enough to set times, and work for directories too
+        if let Ok(file) = File::open(dst) {
+            // Not every filesystem supports this, which isn't worth failing the copy over
+            let _ = file.set_times(FileTimes::new().set_modified(modified));
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `src` to `dst`, falling back to a copy followed by a delete when they are on different
+/// filesystems
+pub fn move_path(src: &Path, dst: &Path) -> Result<()> {
+    match fs::rename(src, dst) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_recursive(src, dst)?;
+            remove_path(src)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fails if something already exists at `path`, since it would be overwritten
+pub fn ensure_free(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", path.to_string_lossy()),
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Removes a file, symlink or whole directory tree
+pub fn remove_path(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// How many entries are looked at to add up the size of a directory tree, since big trees would
+/// take too long to wait for
+pub const USAGE_BUDGET: usize = 10000;
+
+/// Returns the total size of a file or directory tree without following symlinks, looking at up
+/// to `budget` entries and skipping any that can't be read. The budget is used up if the total is
+/// only part of the tree's size.
+pub fn disk_usage_within(path: &Path, budget: &mut usize) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+
+    let mut total = meta.len();
+    if meta.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+
+            total += disk_usage_within(&entry.path(), budget);
+        }
+    }
+
+    total
+}
+
+/// Finds the first free name like `name (1).ext` next to `path`, skipping any name for which
+/// `taken` returns true. Directory names (`dir`) are numbered at the end, since a dot in them
+/// doesn't start an extension.
+pub fn unique_path(path: &Path, dir: bool, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let name = |p: Option<&std::ffi::OsStr>| {
+        p.map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let (stem, ext) = if dir {
+        (name(path.file_name()), String::new())
+    } else {
+        (
+            name(path.file_stem()),
+            path.extension()
+                .map(|e| format!(".{}", e.to_string_lossy()))
+                .unwrap_or_default(),
+        )
+    };
+
+    (1..)
+        .map(|i| path.with_file_name(format!("{} ({}){}", stem, i, ext)))
+        .find(|p| fs::symlink_metadata(p).is_err() && !taken(p))
+        .expect("Ran out of names")
+}
+
+/// Creates a new directory under the system's temporary directory that only the current user can
+/// get into, so that other users can't put files or symlinks where they'll be written
+pub fn private_temp_dir() -> Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+
+    #[cfg(unix)]
+    let builder = {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let mut builder = DirBuilder::new();
+        builder.mode(0o700);
+        builder
+    };
+    // Other platforms keep each user's temporary directory private already
+    #[cfg(not(unix))]
+    let builder = DirBuilder::new();
+
+    // Creating the directory fails if anything is already there, even a dangling symlink
+    let mut attempt = 0;
+    loop {
+        let dir = env::temp_dir().join(format!("thunars-{}-{}-{}", process::id(), nanos, attempt));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// A private temporary directory for tests, removed along with everything in it when dropped,
+/// even if the test panics
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn create() -> Self {
+        Self(private_temp_dir().unwrap())
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
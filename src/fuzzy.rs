@@ -0,0 +1,260 @@
+// Scores are loosely modelled on fzf's: every matched character is worth the same, matches right
+// after a separator or at the start of a word are worth more, and gaps between matched
+// characters cost a little.
+const SCORE_MATCH: i64 = 16;
+const PENALTY_GAP: i64 = 1;
+const BONUS_CONSECUTIVE: i64 = 6;
+const BONUS_SEGMENT: i64 = 12; // start of a path segment
+const BONUS_WORD: i64 = 8; // after `_`, `-`, `.` or a space
+const BONUS_CAMEL: i64 = 6; // `fooBar` or `foo2`
+
+/// A candidate that matched a query, along with the positions (in chars) of the matched
+/// characters so they can be highlighted
+#[derive(Clone)]
+pub struct Match {
+    text: String,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+impl Match {
+    /// A match without a score or anything to highlight, for results that weren't filtered
+    pub fn plain(text: String) -> Self {
+        Self {
+            text,
+            score: 0,
+            positions: Vec::new(),
+        }
+    }
+
+    /// A match without a score, highlighting the chars at `positions`
+    pub fn highlighted(text: String, positions: Vec<usize>) -> Self {
+        Self {
+            text,
+            score: 0,
+            positions,
+        }
+    }
+
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+}
+
+fn bonus(prev: Option<char>, curr: char) -> i64 {
+    match prev {
+        None | Some('/') => BONUS_SEGMENT,
+        Some('_' | '-' | '.' | ' ') => BONUS_WORD,
+        Some(p) if p.is_lowercase() && curr.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_ascii_digit() && curr.is_ascii_digit() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+/// Fuzzy matches `query` against `candidate`, returning `None` unless every character of the
+/// query appears in order. Matching ignores case unless the query has an uppercase letter.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<Match> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let query = query.chars().map(fold).collect::<Vec<_>>();
+    let chars = candidate.chars().collect::<Vec<_>>();
+    let (m, n) = (query.len(), chars.len());
+
+    if m == 0 {
+        return Some(Match::plain(candidate.to_string()));
+    }
+    if m > n {
+        return None;
+    }
+
+    let folded = chars.iter().map(|c| fold(*c)).collect::<Vec<_>>();
+
+    // Most candidates don't match at all, which is much cheaper to rule out than to score
+    let mut rest = folded.iter();
+    if !query.iter().all(|q| rest.any(|c| c == q)) {
+        return None;
+    }
+
+    let bonuses = (0..n)
+        .map(|j| bonus(j.checked_sub(1).map(|p| chars[p]), chars[j]))
+        .collect::<Vec<_>>();
+
+    // scores[i][j] is the best score for matching query[..=i] with query[i] at chars[j], and
+    // from[i][j] is where query[i - 1] was matched in that case
+    let mut scores = vec![vec![None; n]; m];
+    let mut from = vec![vec![0; n]; m];
+
+    for (j, c) in folded.iter().enumerate() {
+        if *c == query[0] {
+            scores[0][j] = Some(SCORE_MATCH + bonuses[j]);
+        }
+    }
+
+    for i in 1..m {
+        // Best way to reach the current column after a gap, as (score, column)
+        let mut gapped: Option<(i64, usize)> = None;
+
+        for j in i..n {
+            if folded[j] == query[i] {
+                let consecutive = scores[i - 1][j - 1].map(|s| (s + BONUS_CONSECUTIVE, j - 1));
+                let best = match (consecutive, gapped) {
+                    (Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+                    (c, g) => c.or(g),
+                };
+
+                if let Some((score, k)) = best {
+                    scores[i][j] = Some(score + SCORE_MATCH + bonuses[j]);
+                    from[i][j] = k;
+                }
+            }
+
+            // Skipping chars[j] on the way to the next column
+            gapped = match (gapped, scores[i - 1][j - 1]) {
+                (Some((g, k)), Some(s)) if g >= s => Some((g - PENALTY_GAP, k)),
+                (_, Some(s)) => Some((s - PENALTY_GAP, j - 1)),
+                (g, None) => g.map(|(g, k)| (g - PENALTY_GAP, k)),
+            };
+        }
+    }
+
+    let (end, score) = scores[m - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|(j, s)| (*s, std::cmp::Reverse(*j)))?;
+
+    let mut positions = vec![end];
+    for i in (1..m).rev() {
+        positions.push(from[i][positions[positions.len() - 1]]);
+    }
+    positions.reverse();
+
+    Some(Match {
+        text: candidate.to_string(),
+        score,
+        positions,
+    })
+}
+
+/// Sorts `matches` best first, keeping only the first `limit`. Shorter candidates win ties.
+pub fn rank(matches: &mut Vec<Match>, limit: usize) {
+    matches.sort_by_key(|m| (std::cmp::Reverse(m.score), m.text.len()));
+    matches.truncate(limit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, candidate).map(|m| m.positions)
+    }
+
+    // Ranks `candidates` against `query`, best first, leaving out ones that don't match
+    fn ranked(query: &str, candidates: &[&str]) -> Vec<String> {
+        let mut matches = candidates
+            .iter()
+            .filter_map(|c| fuzzy_match(query, c))
+            .collect::<Vec<_>>();
+        rank(&mut matches, usize::MAX);
+
+        matches.into_iter().map(|m| m.text).collect()
+    }
+
+    #[test]
+    fn no_match() {
+        assert!(fuzzy_match("xyz", "abc").is_none());
+        assert!(fuzzy_match("ba", "ab").is_none());
+        assert!(fuzzy_match("abcd", "abc").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let m = fuzzy_match("", "abc").unwrap();
+        assert_eq!(m.text(), "abc");
+        assert!(m.positions().is_empty());
+    }
+
+    #[test]
+    fn smart_case() {
+        assert_eq!(
+            positions("readme", "README.md"),
+            Some(vec![0, 1, 2, 3, 4, 5])
+        );
+        assert_eq!(positions("Read", "README.md"), None);
+        assert_eq!(positions("Read", "src/Reader.rs"), Some(vec![4, 5, 6, 7]));
+        assert_eq!(positions("R", "read"), None);
+    }
+
+    #[test]
+    fn positions_follow_the_best_alignment() {
+        // The first `b` is at the start too, but `br` together after the slash is better
+        assert_eq!(positions("br", "bar/brew"), Some(vec![4, 5]));
+        assert_eq!(positions("fb", "foo/bar"), Some(vec![0, 4]));
+        assert_eq!(positions("ab", "xa_b"), Some(vec![1, 3]));
+        // Multibyte characters count as one position each
+        assert_eq!(positions("éb", "café/bar"), Some(vec![3, 5]));
+    }
+
+    #[test]
+    fn segment_starts_rank_first() {
+        assert_eq!(
+            ranked("bar", &["foobar.rs", "foo/bar.rs"]),
+            ["foo/bar.rs", "foobar.rs"]
+        );
+    }
+
+    #[test]
+    fn word_starts_rank_first() {
+        assert_eq!(
+            ranked("fb", &["xfoxbox", "x_foo_bar"]),
+            ["x_foo_bar", "xfoxbox"]
+        );
+    }
+
+    #[test]
+    fn camel_case_and_digits_rank_first() {
+        assert_eq!(
+            ranked("fb", &["xfoobar", "xfooBar"]),
+            ["xfooBar", "xfoobar"]
+        );
+        assert_eq!(
+            ranked("v2", &["v_a2", "vxx2", "v2"]),
+            ["v2", "v_a2", "vxx2"]
+        );
+    }
+
+    #[test]
+    fn consecutive_matches_rank_first() {
+        assert_eq!(
+            ranked("abc", &["axbxcx", "abcxxx", "axxbcx"]),
+            ["abcxxx", "axxbcx", "axbxcx"]
+        );
+    }
+
+    #[test]
+    fn ties_go_to_shorter_candidates() {
+        let mut matches = ["src/lib.rs", "lib.rs", "src/main/lib.rs"]
+            .iter()
+            .filter_map(|c| fuzzy_match("lib", c))
+            .collect::<Vec<_>>();
+        rank(&mut matches, 2);
+
+        let texts = matches
+            .iter()
+            .map(|m| m.text().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["lib.rs", "src/lib.rs"]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use crate::Result;
+
+const THEME: &str = "base16-ocean.dark";
+
+// Tabs are replaced, since the terminal would otherwise decide how wide they are
+pub const TAB: &str = "    ";
+
+// How many lines before the first one shown are parsed, so that a preview far into a big file
+// doesn't have to parse everything above it
+const CONTEXT_LINES: usize = 200;
+
+const GUTTER_STYLE: Style = Style::new().fg(Color::DarkGray);
+const HIGHLIGHT_BG: Color = Color::Rgb(0x4f, 0x5b, 0x66);
+
+/// Syntax highlights files for the preview, detecting the language from the file's name or
+/// extension, or its shebang line
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(THEME).expect("Missing default theme"),
+        }
+    }
+
+    fn syntax(&self, path: &Path, first_line: &str) -> &SyntaxReference {
+        let by_name = |name: Option<&std::ffi::OsStr>| {
+            name.and_then(|n| self.syntaxes.find_syntax_by_extension(&n.to_string_lossy()))
+        };
+
+        // Names like `Makefile` are listed as extensions too
+        by_name(path.file_name())
+            .or_else(|| by_name(path.extension()))
+            .or_else(|| self.syntaxes.find_syntax_by_first_line(first_line))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    /// Highlights `count` lines of `path` starting at `start` (counting from 1), with line
+    /// numbers. Line `mark` is given a background, if it's shown.
+    pub fn highlight(
+        &self,
+        path: &Path,
+        start: usize,
+        count: usize,
+        mark: Option<usize>,
+    ) -> Result<Vec<Line<'static>>> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let end = start + count;
+        let width = end.to_string().len();
+
+        let mut highlighter = None;
+        let mut lines = Vec::new();
+        let mut bytes = Vec::new();
+
+        // The syntax is detected from the first line
+        let mut first_line = Vec::new();
+
+        // Lines before `start` are still highlighted, since they can change how it's parsed (like
+        // an unclosed comment)
+        for number in 1..end {
+            bytes.clear();
+            if reader.read_until(b'\n', &mut bytes)? == 0 {
+                break;
+            }
+
+            if number == 1 {
+                first_line.clone_from(&bytes);
+            }
+            if number + CONTEXT_LINES < start {
+                continue;
+            }
+            let text = String::from_utf8_lossy(&bytes);
+
+            let highlighter = highlighter.get_or_insert_with(|| {
+                let syntax = self.syntax(path, &String::from_utf8_lossy(&first_line));
+                HighlightLines::new(syntax, &self.theme)
+            });
+            let ranges = highlighter.highlight_line(&text, &self.syntaxes)?;
+
+            if number < start {
+                continue;
+            }
+
+            let mut spans = vec![Span::styled(
+                format!("{:>width$} │ ", number, width = width),
+                GUTTER_STYLE,
+            )];
+            spans.extend(ranges.into_iter().map(|(style, s)| {
+                let content = s.trim_end_matches(['\n', '\r']).replace('\t', TAB);
+                Span::styled(content, convert_style(style))
+            }));
+
+            let line = Line::from(spans);
+            lines.push(if mark == Some(number) {
+                line.style(Style::new().bg(HIGHLIGHT_BG))
+            } else {
+                line
+            });
+        }
+
+        Ok(lines)
+    }
+}
+
+// Backgrounds are left to the terminal so the preview matches the rest of the window
+fn convert_style(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let mut converted = Style::new().fg(Color::Rgb(fg.r, fg.g, fg.b));
+
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+
+    converted
+}
@@ -0,0 +1,523 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    result,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fsops, load_toml, save_toml,
+    trash::{Trash, TrashEntry},
+    vfs::{self, Progress},
+    xdg_dir, Error, Result,
+};
+
+// Older operations are forgotten once the journal grows past this
+const MAX_OPERATIONS: usize = 100;
+
+/// A reversible file operation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    Rename { from: PathBuf, to: PathBuf },
+    Move { from: PathBuf, to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+    Mkdir { path: PathBuf },
+    Touch { path: PathBuf },
+    Trash { entry: TrashEntry },
+    Compress { sources: Vec<PathBuf>, to: PathBuf },
+    Extract { archive: PathBuf, to: PathBuf },
+    Batch { operations: Vec<Operation> }, // several operations done by one command
+}
+
+impl Operation {
+    pub fn describe(&self) -> String {
+        let name = |p: &Path| {
+            p.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        };
+
+        match self {
+            Operation::Rename { from, to } => format!("renaming {} to {}", name(from), name(to)),
+            Operation::Move { from, .. } => format!("move of {}", name(from)),
+            Operation::Copy { from, .. } => format!("copy of {}", name(from)),
+            Operation::Mkdir { path } => format!("creation of {}", name(path)),
+            Operation::Touch { path } => format!("creation of {}", name(path)),
+            Operation::Trash { entry } => format!("deletion of {}", name(entry.original())),
+            Operation::Compress { to, .. } => format!("creation of {}", name(to)),
+            Operation::Extract { archive, .. } => format!("extraction of {}", name(archive)),
+            Operation::Batch { operations } if operations.len() == 1 => operations[0].describe(),
+            Operation::Batch { operations } => format!("{} operations", operations.len()),
+        }
+    }
+
+    /// Reverses the operation, returning what should be stored to redo it later. If part of a
+    /// batch fails, the parts that were reversed and the parts that weren't are kept apart.
+    fn undo(self, trash: &Trash) -> result::Result<Self, Box<Partial>> {
+        let Operation::Batch { operations } = self else {
+            return self.undo_single(trash).map_err(|error| {
+                Box::new(Partial {
+                    remaining: Some(self),
+                    done: None,
+                    error,
+                })
+            });
+        };
+
+        // Undone back to front, so `undone` is built up in reverse
+        let mut remaining = operations;
+        let mut undone = Vec::new();
+        while let Some(op) = remaining.pop() {
+            match op.undo(trash) {
+                Ok(op) => undone.push(op),
+                Err(partial) => {
+                    remaining.extend(partial.remaining);
+                    undone.extend(partial.done);
+                    undone.reverse();
+
+                    return Err(Box::new(Partial {
+                        remaining: Operation::batch(remaining),
+                        done: Operation::batch(undone),
+                        error: partial.error,
+                    }));
+                }
+            }
+        }
+        undone.reverse();
+
+        Ok(Operation::Batch { operations: undone })
+    }
+
+    fn undo_single(&self, trash: &Trash) -> Result<Self> {
+        match self {
+            Operation::Rename { from, to } => {
+                fsops::ensure_free(from)?;
+                fs::rename(to, from)?;
+            }
+            Operation::Move { from, to } => {
+                fsops::ensure_free(from)?;
+                fsops::move_path(to, from)?;
+            }
+            // The copy may have been changed since, so it's trashed rather than removed
+            Operation::Copy { to, .. }
+            | Operation::Touch { path: to }
+            | Operation::Compress { to, .. }
+            | Operation::Extract { to, .. } => {
+                trash.trash(to)?;
+            }
+            Operation::Mkdir { path } => fs::remove_dir(path)?,
+            Operation::Trash { entry } => trash.restore(entry)?,
+            Operation::Batch { .. } => unreachable!("Batches are undone one operation at a time"),
+        }
+
+        Ok(self.clone())
+    }
+
+    /// Performs the operation again after it was undone, returning what should be stored to
+    /// undo it again. Partly failed batches are split up like they are by `undo`.
+    fn redo(self, trash: &Trash) -> result::Result<Self, Box<Partial>> {
+        let Operation::Batch { operations } = self else {
+            return self.redo_single(trash).map_err(|error| {
+                Box::new(Partial {
+                    remaining: Some(self),
+                    done: None,
+                    error,
+                })
+            });
+        };
+
+        let mut remaining = operations.into_iter();
+        let mut redone = Vec::new();
+        while let Some(op) = remaining.next() {
+            match op.redo(trash) {
+                Ok(op) => redone.push(op),
+                Err(partial) => {
+                    redone.extend(partial.done);
+
+                    return Err(Box::new(Partial {
+                        remaining: Operation::batch(
+                            partial.remaining.into_iter().chain(remaining).collect(),
+                        ),
+                        done: Operation::batch(redone),
+                        error: partial.error,
+                    }));
+                }
+            }
+        }
+
+        Ok(Operation::Batch { operations: redone })
+    }
+
+    fn redo_single(&self, trash: &Trash) -> Result<Self> {
+        match self {
+            Operation::Rename { from, to } => {
+                fsops::ensure_free(to)?;
+                fs::rename(from, to)?;
+            }
+            Operation::Move { from, to } => {
+                fsops::ensure_free(to)?;
+                fsops::move_path(from, to)?;
+            }
+            Operation::Copy { from, to } => {
+                fsops::ensure_free(to)?;
+                vfs::copy(from, to)?;
+            }
+            Operation::Mkdir { path } => fs::create_dir(path)?,
+            Operation::Touch { path } => {
+                File::create_new(path)?;
+            }
+            Operation::Compress { sources, to } => {
+                vfs::compress(sources, to, &Progress::default())?
+            }
+            Operation::Extract { archive, to } => {
+                vfs::extract_all(archive, to, &Progress::default())?
+            }
+            // Trashing again creates a new trash entry
+            Operation::Trash { entry } => {
+                return Ok(Operation::Trash {
+                    entry: trash.trash(entry.original())?,
+                })
+            }
+            Operation::Batch { .. } => unreachable!("Batches are redone one operation at a time"),
+        }
+
+        Ok(self.clone())
+    }
+
+    // Wraps what's left of a split up batch, if there's anything
+    fn batch(operations: Vec<Operation>) -> Option<Self> {
+        (!operations.is_empty()).then_some(Operation::Batch { operations })
+    }
+}
+
+// An operation that failed part way through
+struct Partial {
+    remaining: Option<Operation>, // what wasn't done, in the same form as before
+    done: Option<Operation>,      // what was, in the form that reverses it
+    error: Error,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct History {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+/// Undo and redo history for file operations, kept on disk so it survives restarts
+pub struct Journal {
+    path: PathBuf,
+    history: History,
+}
+
+impl Journal {
+    /// Loads the journal from `$XDG_STATE_HOME/thunars`, which defaults to `~/.local/state`
+    pub fn init() -> Result<Self> {
+        Self::at(xdg_dir("XDG_STATE_HOME", ".local/state")?.join("thunars"))
+    }
+
+    /// Loads the journal kept in `dir`, creating the directory if needed
+    pub fn at(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join("journal.toml");
+        let history = load_toml(&path)?;
+
+        Ok(Self { path, history })
+    }
+
+    fn save(&self) -> Result<()> {
+        save_toml(&self.path, &self.history)
+    }
+
+    pub fn record(&mut self, op: Operation) -> Result<()> {
+        if let Operation::Batch { operations } = &op {
+            if operations.is_empty() {
+                return Ok(());
+            }
+        }
+
+        self.history.undo.push(op);
+        self.history.redo.clear();
+
+        let len = self.history.undo.len();
+        if len > MAX_OPERATIONS {
+            self.history.undo.drain(..len - MAX_OPERATIONS);
+        }
+
+        self.save()
+    }
+
+    /// Undoes the most recent operation, returning its description
+    pub fn undo(&mut self, trash: &Trash) -> Result<Option<String>> {
+        let Some(op) = self.history.undo.pop() else {
+            return Ok(None);
+        };
+        let description = op.describe();
+
+        // Whatever was undone before a failure can be redone, and the rest can be tried again
+        let result = match op.undo(trash) {
+            Ok(undone) => {
+                self.history.redo.push(undone);
+                Ok(Some(description))
+            }
+            Err(partial) => {
+                self.history.undo.extend(partial.remaining);
+                self.history.redo.extend(partial.done);
+                Err(partial.error)
+            }
+        };
+
+        self.save()?;
+
+        result
+    }
+
+    /// Redoes the most recently undone operation, returning its description
+    pub fn redo(&mut self, trash: &Trash) -> Result<Option<String>> {
+        let Some(op) = self.history.redo.pop() else {
+            return Ok(None);
+        };
+        let description = op.describe();
+
+        let result = match op.redo(trash) {
+            Ok(redone) => {
+                self.history.undo.push(redone);
+                Ok(Some(description))
+            }
+            Err(partial) => {
+                self.history.redo.extend(partial.remaining);
+                self.history.undo.extend(partial.done);
+                Err(partial.error)
+            }
+        };
+
+        self.save()?;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsops::TempDir;
+
+    // A journal and trash in their own temporary directory, along with a directory to work in
+    fn scratch() -> (TempDir, Journal, Trash) {
+        let root = TempDir::create();
+        let journal = Journal::at(root.join("state")).unwrap();
+        let trash = Trash::at(root.join("Trash")).unwrap();
+        fs::create_dir(root.join("home")).unwrap();
+
+        (root, journal, trash)
+    }
+
+    // Operations don't implement `PartialEq`, so stacks are compared by how they're printed
+    fn stack(ops: &[Operation]) -> String {
+        format!("{:?}", ops)
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let (root, mut journal, trash) = scratch();
+        let home = root.join("home");
+        fs::create_dir(home.join("dir")).unwrap();
+        for name in ["renamed", "moved", "copied", "trashed"] {
+            fs::write(home.join(name), name).unwrap();
+        }
+
+        fs::rename(home.join("renamed"), home.join("renamed2")).unwrap();
+        journal
+            .record(Operation::Rename {
+                from: home.join("renamed"),
+                to: home.join("renamed2"),
+            })
+            .unwrap();
+        fsops::move_path(&home.join("moved"), &home.join("dir/moved")).unwrap();
+        journal
+            .record(Operation::Move {
+                from: home.join("moved"),
+                to: home.join("dir/moved"),
+            })
+            .unwrap();
+        vfs::copy(&home.join("copied"), &home.join("dir/copied")).unwrap();
+        journal
+            .record(Operation::Copy {
+                from: home.join("copied"),
+                to: home.join("dir/copied"),
+            })
+            .unwrap();
+        let entry = trash.trash(&home.join("trashed")).unwrap();
+        journal.record(Operation::Trash { entry }).unwrap();
+
+        // What exists with everything done, and with everything undone
+        let done = ["renamed2", "dir/moved", "copied", "dir/copied"];
+        let undone = ["renamed", "moved", "copied", "trashed"];
+        let exist = |paths: &[&str]| paths.iter().all(|p| home.join(p).exists());
+        let gone = |paths: &[&str]| paths.iter().all(|p| !home.join(p).exists());
+
+        for _ in 0..4 {
+            assert!(journal.undo(&trash).unwrap().is_some());
+        }
+        assert!(journal.undo(&trash).unwrap().is_none());
+        assert!(exist(&undone) && gone(&["renamed2", "dir/moved", "dir/copied"]));
+        assert_eq!(fs::read_to_string(home.join("trashed")).unwrap(), "trashed");
+        // The copy went to the trash in case it had been changed
+        assert_eq!(trash.list().unwrap().len(), 1);
+
+        for _ in 0..4 {
+            assert!(journal.redo(&trash).unwrap().is_some());
+        }
+        assert!(journal.redo(&trash).unwrap().is_none());
+        assert!(exist(&done) && gone(&["renamed", "moved", "trashed"]));
+        assert_eq!(
+            fs::read_to_string(home.join("dir/copied")).unwrap(),
+            "copied"
+        );
+        assert_eq!(trash.list().unwrap().len(), 2);
+
+        // Redoing the trashing made a new entry, which undoing again restores
+        for _ in 0..4 {
+            journal.undo(&trash).unwrap();
+        }
+        assert!(exist(&undone));
+    }
+
+    #[test]
+    fn partly_failed_batch_keeps_the_rest() {
+        let (root, mut journal, trash) = scratch();
+        let home = root.join("home");
+        let rename = |name: &str| Operation::Rename {
+            from: home.join(name),
+            to: home.join(format!("{}2", name)),
+        };
+
+        for name in ["a", "b", "c"] {
+            fs::write(home.join(format!("{}2", name)), name).unwrap();
+        }
+        journal
+            .record(Operation::Batch {
+                operations: vec![rename("a"), rename("b"), rename("c")],
+            })
+            .unwrap();
+
+        // Batches are undone back to front, so `c` is undone before `b` fails
+        fs::write(home.join("b"), "in the way").unwrap();
+        assert!(journal.undo(&trash).is_err());
+        assert_eq!(
+            stack(&journal.history.undo),
+            stack(&[Operation::Batch {
+                operations: vec![rename("a"), rename("b")],
+            }])
+        );
+        assert_eq!(
+            stack(&journal.history.redo),
+            stack(&[Operation::Batch {
+                operations: vec![rename("c")],
+            }])
+        );
+        assert!(home.join("c").exists() && home.join("a2").exists());
+
+        // Only what's left is tried again
+        fs::remove_file(home.join("b")).unwrap();
+        journal.undo(&trash).unwrap();
+        assert!(journal.history.undo.is_empty());
+        assert_eq!(journal.history.redo.len(), 2);
+        assert!(["a", "b", "c"].iter().all(|n| home.join(n).exists()));
+
+        // Redoing splits up the same way, front to back
+        fs::write(home.join("b2"), "in the way").unwrap();
+        assert!(journal.redo(&trash).is_err());
+        assert_eq!(
+            stack(&journal.history.redo),
+            stack(&[
+                Operation::Batch {
+                    operations: vec![rename("c")],
+                },
+                Operation::Batch {
+                    operations: vec![rename("b")],
+                },
+            ])
+        );
+        assert_eq!(
+            stack(&journal.history.undo),
+            stack(&[Operation::Batch {
+                operations: vec![rename("a")],
+            }])
+        );
+    }
+
+    #[test]
+    fn oldest_operations_are_forgotten() {
+        let (root, mut journal, trash) = scratch();
+        let mkdir = |i: usize| Operation::Mkdir {
+            path: root.join(format!("home/{}", i)),
+        };
+
+        for i in 0..MAX_OPERATIONS + 5 {
+            journal.record(mkdir(i)).unwrap();
+        }
+        assert_eq!(journal.history.undo.len(), MAX_OPERATIONS);
+        assert_eq!(stack(&journal.history.undo[..1]), stack(&[mkdir(5)]));
+
+        // Recording something new forgets what was undone
+        fs::create_dir(root.join("home/104")).unwrap();
+        journal.undo(&trash).unwrap();
+        assert_eq!(journal.history.redo.len(), 1);
+        journal.record(mkdir(200)).unwrap();
+        assert!(journal.history.redo.is_empty());
+    }
+
+    #[test]
+    fn history_is_reloaded() {
+        let (root, mut journal, trash) = scratch();
+        let dir = root.join("home/dir");
+        fs::create_dir(&dir).unwrap();
+        journal
+            .record(Operation::Mkdir { path: dir.clone() })
+            .unwrap();
+        journal
+            .record(Operation::Batch {
+                operations: vec![
+                    Operation::Touch {
+                        path: root.join("home/a"),
+                    },
+                    Operation::Trash {
+                        entry: trash.trash(&dir).unwrap(),
+                    },
+                ],
+            })
+            .unwrap();
+
+        let reloaded = Journal::at(root.join("state")).unwrap();
+        assert_eq!(stack(&reloaded.history.undo), stack(&journal.history.undo));
+        assert!(reloaded.history.redo.is_empty());
+    }
+
+    #[test]
+    fn unreadable_journal_is_moved_aside() {
+        let (root, _, _) = scratch();
+        let path = root.join("state/journal.toml");
+        fs::write(&path, "not a journal").unwrap();
+
+        let mut journal = Journal::at(root.join("state")).unwrap();
+        assert!(journal.history.undo.is_empty());
+        journal
+            .record(Operation::Mkdir {
+                path: root.join("home/dir"),
+            })
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.join("state/journal.toml.bad")).unwrap(),
+            "not a journal"
+        );
+        assert_eq!(
+            Journal::at(root.join("state")).unwrap().history.undo.len(),
+            1
+        );
+    }
+}
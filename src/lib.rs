@@ -1,19 +1,49 @@
+mod ansi;
+mod associations;
 pub mod browser;
 mod components;
 pub mod config;
+mod frecency;
+mod fsops;
+mod fuzzy;
+mod highlight;
+mod journal;
+pub mod opener;
+mod preview;
+mod rename;
+mod search;
+mod trash;
 pub mod tui;
+mod vfs;
 
 use std::{
+    env,
     fmt::{self, Display, Formatter},
-    io, result,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    result,
 };
 
-use homedir::GetHomeError;
+use globset::Error as GlobError;
+use homedir::{my_home, GetHomeError};
+use ignore::Error as IgnoreError;
+use image::ImageError;
+use regex::Error as RegexError;
+use serde::{de::DeserializeOwned, Serialize};
+use syntect::Error as HighlightError;
+use zip::result::ZipError;
 
 #[derive(Debug)]
 pub enum Error {
     IOError(io::Error),
     GetHomeError(GetHomeError),
+    GlobError(GlobError),
+    RegexError(RegexError),
+    IgnoreError(IgnoreError),
+    HighlightError(HighlightError),
+    ImageError(ImageError),
+    ZipError(ZipError),
 }
 
 impl From<io::Error> for Error {
@@ -28,17 +58,101 @@ impl From<GetHomeError> for Error {
     }
 }
 
+impl From<GlobError> for Error {
+    fn from(value: GlobError) -> Self {
+        Self::GlobError(value)
+    }
+}
+
+impl From<RegexError> for Error {
+    fn from(value: RegexError) -> Self {
+        Self::RegexError(value)
+    }
+}
+
+impl From<IgnoreError> for Error {
+    fn from(value: IgnoreError) -> Self {
+        Self::IgnoreError(value)
+    }
+}
+
+impl From<HighlightError> for Error {
+    fn from(value: HighlightError) -> Self {
+        Self::HighlightError(value)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(value: ImageError) -> Self {
+        Self::ImageError(value)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(value: ZipError) -> Self {
+        Self::ZipError(value)
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Error::IOError(e) => f.write_fmt(format_args!("{}", e)),
             Error::GetHomeError(e) => f.write_fmt(format_args!("{}", e)),
+            Error::GlobError(e) => f.write_fmt(format_args!("{}", e)),
+            // The full message points at the error over several lines, which the status line
+            // doesn't have room for
+            Error::RegexError(e) => f.write_str(e.to_string().lines().last().unwrap_or_default()),
+            Error::IgnoreError(e) => f.write_fmt(format_args!("{}", e)),
+            Error::HighlightError(e) => f.write_fmt(format_args!("{}", e)),
+            Error::ImageError(e) => f.write_fmt(format_args!("{}", e)),
+            Error::ZipError(e) => f.write_fmt(format_args!("{}", e)),
         }
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// Returns the XDG base directory in `var`, or `fallback` under the home directory if it's unset
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf> {
+    match env::var_os(var) {
+        Some(d) if !d.is_empty() => Ok(PathBuf::from(d)),
+        _ => Ok(my_home()?
+            .expect("Unable to find home directory")
+            .join(fallback)),
+    }
+}
+
+/// Reads state kept in a TOML file, like the undo journal, or the default if there's none yet.
+/// State we can't parse is moved aside to a `.bad` file and started over, rather than refusing to
+/// start or overwriting it the next time it's saved.
+fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let state = match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).ok(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) if e.kind() == ErrorKind::InvalidData => None, // not UTF-8
+        Err(e) => return Err(e.into()),
+    };
+
+    match state {
+        Some(state) => Ok(state),
+        None => {
+            let mut bad = path.as_os_str().to_owned();
+            bad.push(".bad");
+            fs::rename(path, bad)?;
+
+            Ok(T::default())
+        }
+    }
+}
+
+fn save_toml<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let contents = toml::to_string(value).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
@@ -0,0 +1,140 @@
+use std::{env, path::Path, process::Command};
+
+use toml::Value;
+
+// Editors that open a file at line N when given `+N` before it
+const LINE_EDITORS: [&str; 7] = ["vi", "vim", "nvim", "nano", "emacs", "hx", "kak"];
+
+/// A command template used to open files. Every `{}` argument is replaced by the file path, and
+/// the path is appended as the last argument if no `{}` is present. `{line}` is replaced by the
+/// line to open the file at, which is 1 unless it was found by a content search.
+#[derive(Clone, Debug)]
+pub struct Opener {
+    command: Vec<String>,
+    terminal: bool, // true if the program needs the terminal, so the TUI is suspended while it runs
+}
+
+impl Opener {
+    pub fn new(command: Vec<String>, terminal: bool) -> Option<Self> {
+        if command.is_empty() {
+            None
+        } else {
+            Some(Self { command, terminal })
+        }
+    }
+
+    /// Parses an opener from a config value, either a plain command like `["nvim", "{}"]` or a
+    /// table like `{ command = ["nvim", "{}"], terminal = true }`
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let (command, terminal) = match value {
+            Value::Array(a) => (a, false),
+            Value::Table(t) => (
+                t.get("command")?.as_array()?,
+                match t.get("terminal") {
+                    Some(v) => v.as_bool()?,
+                    None => false,
+                },
+            ),
+            _ => return None,
+        };
+
+        let command = command
+            .iter()
+            .map(|v| v.as_str().map(String::from))
+            .collect::<Option<Vec<_>>>()?;
+
+        Self::new(command, terminal)
+    }
+
+    /// Parses a command typed by the user. These run in the terminal unless they end with `&`.
+    pub fn from_input(input: &str) -> Option<Self> {
+        let (input, terminal) = match input.trim_end().strip_suffix('&') {
+            Some(i) => (i, false),
+            None => (input, true),
+        };
+
+        Self::new(
+            input.split_whitespace().map(String::from).collect(),
+            terminal,
+        )
+    }
+
+    /// Parses an opener from an environment variable like `$EDITOR`, splitting on whitespace.
+    /// Editors known to take a `+N` argument open files at the line to show.
+    fn from_env(var: &str) -> Option<Self> {
+        let value = env::var(var).ok()?;
+        let mut command = value
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let program = Path::new(command.first()?).file_name()?.to_string_lossy();
+        if LINE_EDITORS.contains(&program.as_ref()) {
+            command.extend(["+{line}".to_string(), "{}".to_string()]);
+        }
+
+        Self::new(command, true)
+    }
+
+    /// The user's preferred text editor, from `$VISUAL` or `$EDITOR`, defaulting to `vi`
+    pub fn editor() -> Self {
+        Self::from_env("VISUAL")
+            .or_else(|| Self::from_env("EDITOR"))
+            .unwrap_or_else(|| Self {
+                command: vec!["vi".to_string()],
+                terminal: true,
+            })
+    }
+
+    /// The openers tried, in order, when no configured opener matches a file. Text files go to
+    /// `$VISUAL` or `$EDITOR` first, anything else (like images and PDFs) to the system opener.
+    pub fn fallbacks(text: bool) -> Vec<Self> {
+        let system = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        let system = Self::new(vec![system.to_string()], false);
+        let editors = [Self::from_env("VISUAL"), Self::from_env("EDITOR")]
+            .into_iter()
+            .flatten();
+
+        if text {
+            editors.chain(system).collect()
+        } else {
+            system.into_iter().chain(editors).collect()
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.command.join(" ")
+    }
+
+    pub fn terminal(&self) -> bool {
+        self.terminal
+    }
+
+    pub fn command(&self, file: &Path) -> Command {
+        self.command_at(file, 1)
+    }
+
+    /// Like `command`, but opening the file at `line`
+    pub fn command_at(&self, file: &Path, line: usize) -> Command {
+        let file = file.to_string_lossy();
+
+        let mut args = self
+            .command
+            .iter()
+            .map(|a| a.replace("{line}", &line.to_string()).replace("{}", &file))
+            .collect::<Vec<_>>();
+
+        if !self.command.iter().any(|a| a.contains("{}")) {
+            args.push(file.to_string());
+        }
+
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+
+        command
+    }
+}
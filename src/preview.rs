@@ -0,0 +1,406 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use image::{imageops::FilterType, GenericImageView};
+use ratatui::{
+    crossterm::style::Color as CColor,
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use toml::Value;
+
+use crate::{
+    ansi,
+    associations::{is_text, mime_type},
+    components::{format_size, format_time, DIR_COLOR, FILE_COLOR},
+    fsops,
+    highlight::Highlighter,
+    vfs::{self, Format},
+    Result,
+};
+
+// Hex dumps show as many bytes per row as fit in the pane, up to the usual 16
+const MAX_BYTES_PER_ROW: usize = 16;
+
+// Files are shown as hex dumps instead of text if there's a NUL byte or invalid UTF-8 this early
+const TEXT_CHECK_LEN: u64 = 4096;
+
+const LABEL_STYLE: Style = Style::new().fg(Color::DarkGray);
+
+/// What a file is previewed with, either one of the built-in previewers or an external command
+#[derive(Clone, Debug)]
+pub enum Previewer {
+    Text,      // syntax highlighted
+    Hex,       // offsets, bytes and their ASCII characters
+    Image,     // drawn with half blocks
+    Archive,   // the entries of a zip or tar
+    Directory, // the entries of a directory
+    Metadata,  // size, permissions, modification time and type
+    // Each `{}` argument is replaced by the path, `{line}` by the line to show, and `{width}` and
+    // `{height}` by the size of the preview pane
+    Command(Vec<String>),
+}
+
+/// Where in a file to preview, and how much room there is for it
+pub struct Pane {
+    pub line: usize, // the line to show, from a content search, otherwise 1
+    pub page: usize, // how many pages the user has scrolled down, for text and hex dumps
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Previewer {
+    /// Parses a previewer from a config value, either the name of a built-in previewer like
+    /// `"hex"` or a command like `["pdftotext", "{}", "-"]`
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => match s.as_str() {
+                "text" => Some(Self::Text),
+                "hex" => Some(Self::Hex),
+                "image" => Some(Self::Image),
+                "archive" => Some(Self::Archive),
+                "directory" => Some(Self::Directory),
+                "metadata" => Some(Self::Metadata),
+                _ => None,
+            },
+            Value::Array(a) => {
+                let command = a
+                    .iter()
+                    .map(|v| v.as_str().map(String::from))
+                    .collect::<Option<Vec<_>>>()?;
+
+                (!command.is_empty()).then_some(Self::Command(command))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the previewer can show more than the first page
+    pub fn pages(&self) -> bool {
+        matches!(
+            self,
+            Self::Text | Self::Hex | Self::Archive | Self::Directory
+        )
+    }
+
+    /// Previews `path`, returning no lines if `pane.page` is past the end
+    pub fn preview(
+        &self,
+        path: &Path,
+        pane: &Pane,
+        highlighter: &Highlighter,
+    ) -> Result<Vec<Line<'static>>> {
+        match self {
+            Self::Text => {
+                if !starts_with_text(path)? {
+                    return hex(path, pane);
+                }
+
+                // Keep some context above the line being shown
+                let (start, mark) = if pane.line > 1 {
+                    (
+                        pane.line.saturating_sub(pane.height / 3).max(1),
+                        Some(pane.line),
+                    )
+                } else {
+                    (1, None)
+                };
+
+                highlighter.highlight(path, start + pane.page * pane.height, pane.height, mark)
+            }
+            Self::Hex => hex(path, pane),
+            Self::Image => image(path, pane),
+            Self::Archive => archive(path, pane),
+            Self::Directory => directory(path, pane),
+            Self::Metadata => metadata(path),
+            Self::Command(c) => command(c, path, pane),
+        }
+    }
+}
+
+fn starts_with_text(path: &Path) -> Result<bool> {
+    let mut start = Vec::new();
+    File::open(path)?
+        .take(TEXT_CHECK_LEN)
+        .read_to_end(&mut start)?;
+
+    Ok(is_text(&start))
+}
+
+// Only the page being shown is read, so big files can be paged through
+fn hex(path: &Path, pane: &Pane) -> Result<Vec<Line<'static>>> {
+    // Each row is an 8 digit offset and two spaces, then 3 columns for each byte in hex, a space
+    // and a column for each byte as text
+    let per_row = (pane.width.saturating_sub(11) / 4).clamp(1, MAX_BYTES_PER_ROW);
+
+    let mut file = File::open(path)?;
+    let page_len = (pane.height * per_row) as u64;
+
+    let offset = pane.page as u64 * page_len;
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(page_len).read_to_end(&mut bytes)?;
+
+    Ok(bytes
+        .chunks(per_row)
+        .enumerate()
+        .map(|(i, row)| {
+            let hex = row
+                .iter()
+                .map(|b| format!("{:02x} ", b))
+                .collect::<String>();
+            let ascii = row
+                .iter()
+                .map(|b| {
+                    if b.is_ascii_graphic() || *b == b' ' {
+                        *b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+
+            Line::from(vec![
+                Span::styled(
+                    format!("{:08x}  ", offset as usize + i * per_row),
+                    LABEL_STYLE,
+                ),
+                Span::raw(format!("{:<width$} ", hex, width = per_row * 3)),
+                Span::styled(ascii, Style::new().fg(Color::Cyan)),
+            ])
+        })
+        .collect())
+}
+
+// Each character cell shows two pixels, the top one as the foreground of `▀` and the bottom one as
+// the background
+fn image(path: &Path, pane: &Pane) -> Result<Vec<Line<'static>>> {
+    let image = image::open(path)?;
+    let (width, height) = image.dimensions();
+
+    // Only shrink, small images would just be blurry if they were scaled up
+    let scaled = if width as usize > pane.width || height as usize > pane.height * 2 {
+        image.resize(
+            pane.width as u32,
+            pane.height as u32 * 2,
+            FilterType::Triangle,
+        )
+    } else {
+        image
+    }
+    .to_rgb8();
+
+    let pixel = |x, y| {
+        let [r, g, b] = scaled.get_pixel(x, y).0;
+        Color::Rgb(r, g, b)
+    };
+
+    Ok((0..scaled.height())
+        .step_by(2)
+        .map(|y| {
+            Line::from(
+                (0..scaled.width())
+                    .map(|x| {
+                        let style = Style::new().fg(pixel(x, y));
+                        if y + 1 < scaled.height() {
+                            Span::styled("▀", style.bg(pixel(x, y + 1)))
+                        } else {
+                            Span::styled("▀", style)
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect())
+}
+
+fn archive(path: &Path, pane: &Pane) -> Result<Vec<Line<'static>>> {
+    let format = Format::detect(path).ok_or_else(vfs::not_archive)?;
+    let shown = pane.page * pane.height..(pane.page + 1) * pane.height;
+
+    // Entries as (name, size)
+    let entries: Vec<(String, u64)> = if format == Format::Zip {
+        let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+        (shown.start.min(zip.len())..shown.end.min(zip.len()))
+            .map(|i| {
+                zip.by_index_raw(i)
+                    .map(|e| (e.name().to_string(), e.size()))
+            })
+            .collect::<zip::result::ZipResult<_>>()?
+    } else {
+        // Tars are read front to back, so only as much as is shown gets decompressed
+        vfs::open_tar(path, format)?
+            .entries()?
+            .skip(shown.start)
+            .take(shown.len())
+            .map(|e| {
+                let e = e?;
+                Ok((e.path()?.to_string_lossy().to_string(), e.size()))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    Ok(entries
+        .into_iter()
+        .map(|(name, size)| {
+            Line::from(vec![
+                Span::styled(format!("{:>10}  ", format_size(size)), LABEL_STYLE),
+                Span::raw(name),
+            ])
+        })
+        .collect())
+}
+
+// Lists the entries of a directory like the file list does, after a summary of what's in it
+fn directory(path: &Path, pane: &Pane) -> Result<Vec<Line<'static>>> {
+    let (mut dirs, mut files): (Vec<_>, Vec<_>) = fs::read_dir(path)?
+        .flatten()
+        .map(|e| e.path())
+        .partition(|p| p.is_dir());
+    dirs.sort();
+    files.sort();
+
+    let mut budget = fsops::USAGE_BUDGET;
+    let size = fsops::disk_usage_within(path, &mut budget);
+    let size = if budget == 0 {
+        format!("over {}", format_size(size))
+    } else {
+        format_size(size)
+    };
+
+    let summary = format!(
+        "{} director{}, {} file{}, {}",
+        dirs.len(),
+        if dirs.len() == 1 { "y" } else { "ies" },
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+        size
+    );
+
+    let entry = |p: &PathBuf, color: CColor| {
+        let name = p
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        Line::styled(name, Style::new().fg(color.into()))
+    };
+
+    Ok([Line::styled(summary, LABEL_STYLE), Line::raw("")]
+        .into_iter()
+        .chain(dirs.iter().map(|d| entry(d, DIR_COLOR)))
+        .chain(files.iter().map(|f| entry(f, FILE_COLOR)))
+        .skip(pane.page * pane.height)
+        .take(pane.height)
+        .collect())
+}
+
+fn metadata(path: &Path) -> Result<Vec<Line<'static>>> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    let kind = if metadata.is_symlink() {
+        format!("symlink to {}", fs::read_link(path)?.to_string_lossy())
+    } else if metadata.is_dir() {
+        "directory".to_string()
+    } else {
+        mime_type(path)
+    };
+
+    let mut fields = vec![
+        (
+            "Name",
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+        ),
+        ("Type", kind),
+        ("Size", format_size(metadata.len())),
+        ("Permissions", permissions(&metadata)),
+    ];
+    if let Ok(modified) = metadata.modified() {
+        fields.push(("Modified", format_time(modified)));
+    }
+
+    Ok(fields
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!("{:<13}", label), LABEL_STYLE),
+                Span::raw(value),
+            ])
+        })
+        .collect())
+}
+
+// Formats the mode like `ls -l` does, e.g. `rwxr-xr-x`
+#[cfg(unix)]
+fn permissions(metadata: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    (0..9)
+        .map(|i| {
+            if mode & (1 << (8 - i)) == 0 {
+                '-'
+            } else {
+                ['r', 'w', 'x'][i % 3]
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn permissions(metadata: &Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "read-only".to_string()
+    } else {
+        "read-write".to_string()
+    }
+}
+
+// Runs an external previewer, keeping the colours of the lines that fit in the pane
+fn command(template: &[String], path: &Path, pane: &Pane) -> Result<Vec<Line<'static>>> {
+    let file = path.to_string_lossy();
+
+    let mut args = template
+        .iter()
+        .map(|a| {
+            a.replace("{line}", &pane.line.to_string())
+                .replace("{width}", &pane.width.to_string())
+                .replace("{height}", &pane.height.to_string())
+                .replace("{}", &file)
+        })
+        .collect::<Vec<_>>();
+
+    if !template.iter().any(|a| a.contains("{}")) {
+        args.push(file.to_string());
+    }
+
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut output = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        let mut reader = BufReader::new(stdout);
+        for _ in 0..pane.height {
+            if reader.read_until(b'\n', &mut output)? == 0 {
+                break;
+            }
+        }
+    }
+
+    // The rest of the output isn't needed, and may never end
+    let _ = child.kill();
+    child.wait()?;
+
+    Ok(ansi::parse(&String::from_utf8_lossy(&output)))
+}
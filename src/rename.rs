@@ -0,0 +1,529 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{fsops, journal::Operation, Result};
+
+fn invalid(message: String) -> crate::Error {
+    io::Error::new(ErrorKind::InvalidInput, message).into()
+}
+
+/// A validated set of renames within a single directory
+pub struct RenamePlan {
+    dir: PathBuf,
+    renames: Vec<(String, String)>, // (old, new), only for names that actually change
+}
+
+impl RenamePlan {
+    /// Pairs up `old` and `new` names, refusing anything that would lose a file: duplicate
+    /// targets, or targets that exist and aren't being renamed away themselves
+    pub fn new(dir: PathBuf, old: &[String], new: &[String]) -> Result<Self> {
+        if old.len() != new.len() {
+            return Err(invalid(format!(
+                "Expected {} names but got {}",
+                old.len(),
+                new.len()
+            )));
+        }
+
+        let renames = old
+            .iter()
+            .cloned()
+            .zip(new.iter().cloned())
+            .filter(|(o, n)| o != n)
+            .collect::<Vec<_>>();
+
+        let mut targets = HashSet::new();
+        for (_, n) in &renames {
+            if n.is_empty() || n == "." || n == ".." || n.contains('/') {
+                return Err(invalid(format!("{:?} isn't a valid name", n)));
+            }
+
+            if !targets.insert(n) {
+                return Err(invalid(format!("{} is the target of several renames", n)));
+            }
+        }
+
+        let sources = renames.iter().map(|(o, _)| o).collect::<HashSet<_>>();
+        for (_, n) in &renames {
+            if !sources.contains(n) && fs::symlink_metadata(dir.join(n)).is_ok() {
+                return Err(invalid(format!("{} already exists", n)));
+            }
+        }
+
+        Ok(Self { dir, renames })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.renames.len()
+    }
+
+    /// Returns the `(old, new)` pairs, leaving out names that don't change
+    pub fn renames(&self) -> &[(String, String)] {
+        &self.renames
+    }
+
+    /// Describes each rename, like `old → new`
+    pub fn preview(&self) -> Vec<String> {
+        self.renames
+            .iter()
+            .map(|(o, n)| format!("{} → {}", o, n))
+            .collect()
+    }
+
+    /// Performs the renames, pushing the steps onto `operations`. Everything is first moved to a
+    /// temporary name, so cycles like swapping `a` and `b` work. If a step fails, whatever was
+    /// already renamed is put back, and only steps that couldn't be are pushed.
+    pub fn apply(&self, operations: &mut Vec<Operation>) -> Result<()> {
+        let temps = (0..self.renames.len())
+            .map(|i| {
+                self.dir
+                    .join(format!(".thunars-rename-{}-{}", process::id(), i))
+            })
+            .collect::<Vec<_>>();
+        for temp in &temps {
+            fsops::ensure_free(temp)?;
+        }
+
+        let mut done = Vec::new();
+        let result = self.rename_via(&temps, &mut done);
+
+        if result.is_err() {
+            while let Some((from, to)) = done.pop() {
+                if fs::rename(&to, &from).is_err() {
+                    done.push((from, to));
+                    break;
+                }
+            }
+        }
+
+        // Anything that couldn't be put back can still be undone
+        operations.extend(
+            done.into_iter()
+                .map(|(from, to)| Operation::Rename { from, to }),
+        );
+
+        result
+    }
+
+    // Renames everything to `temps` and then to the new names, pushing each `(from, to)` onto
+    // `done`
+    fn rename_via(&self, temps: &[PathBuf], done: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+        for ((old, _), temp) in self.renames.iter().zip(temps) {
+            let from = self.dir.join(old);
+
+            fs::rename(&from, temp)?;
+            done.push((from, temp.clone()));
+        }
+
+        for (temp, (_, new)) in temps.iter().zip(&self.renames) {
+            let to = self.dir.join(new);
+
+            fs::rename(temp, &to)?;
+            done.push((temp.clone(), to));
+        }
+
+        Ok(())
+    }
+}
+
+/// A piece of a rename template
+pub enum Part {
+    Text(String),
+    Counter(usize), // zero-padded to this many digits
+    Name,
+    Ext,
+}
+
+/// A rule for renaming many entries at once, as typed into the omnibar
+pub enum RenamePattern {
+    /// `s/regex/replacement/flags`, where the replacement can use `$1` or `${name}` to refer to
+    /// capture groups. The `g` flag replaces every match instead of the first, and `i` ignores
+    /// case. Names the regex doesn't match are left alone.
+    Substitute {
+        regex: Regex,
+        replacement: String,
+        all: bool,
+    },
+    /// Anything else is a template like `{n:03}-{name}.{ext}`, where `{n}` counts up from 1
+    /// (`{n:03}` pads it to three digits), `{name}` is the name without its extension and `{ext}`
+    /// is the extension. `{{` and `}}` stand for literal braces.
+    Template(Vec<Part>),
+}
+
+impl RenamePattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        match pattern.strip_prefix("s/") {
+            Some(rest) => Self::parse_substitute(rest),
+            None => Self::parse_template(pattern),
+        }
+    }
+
+    fn parse_substitute(rest: &str) -> Result<Self> {
+        // Split on unescaped slashes, unescaping them as we go
+        let mut parts = vec![String::new()];
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('/') => parts.last_mut().unwrap().push('/'),
+                    Some(c) => parts.last_mut().unwrap().extend(['\\', c]),
+                    None => parts.last_mut().unwrap().push('\\'),
+                },
+                '/' => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+
+        let (regex, replacement, flags) = match parts.as_slice() {
+            [r, p] => (r, p, ""),
+            [r, p, f] => (r, p, f.as_str()),
+            _ => return Err(invalid("Expected s/regex/replacement/".to_string())),
+        };
+
+        if let Some(f) = flags.chars().find(|f| !matches!(f, 'g' | 'i')) {
+            return Err(invalid(format!("Unknown flag {}", f)));
+        }
+
+        Ok(Self::Substitute {
+            regex: RegexBuilder::new(regex)
+                .case_insensitive(flags.contains('i'))
+                .build()?,
+            replacement: replacement.clone(),
+            all: flags.contains('g'),
+        })
+    }
+
+    fn parse_template(pattern: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let placeholder = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                    let part = match placeholder.as_str() {
+                        "n" => Part::Counter(0),
+                        "name" => Part::Name,
+                        "ext" => Part::Ext,
+                        p => match p.strip_prefix("n:").map(str::parse) {
+                            Some(Ok(width)) => Part::Counter(width),
+                            _ => return Err(invalid(format!("Unknown placeholder {{{}}}", p))),
+                        },
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err(invalid("Unmatched }, use }} for a literal one".to_string())),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self::Template(parts))
+    }
+
+    /// Returns the new name for `name`, or `None` if the pattern doesn't apply to it. `counter`
+    /// is the value of `{n}`.
+    fn apply(&self, name: &str, counter: usize) -> Option<String> {
+        match self {
+            Self::Substitute {
+                regex,
+                replacement,
+                all,
+            } => {
+                if !regex.is_match(name) {
+                    return None;
+                }
+
+                let limit = if *all { 0 } else { 1 };
+                Some(regex.replacen(name, limit, replacement).to_string())
+            }
+            Self::Template(parts) => {
+                let path = Path::new(name);
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let ext = path.extension().map(|e| e.to_string_lossy());
+
+                let mut new = String::new();
+                for part in parts {
+                    match part {
+                        Part::Text(t) => new.push_str(t),
+                        Part::Counter(width) => new.push_str(&format!("{:0width$}", counter)),
+                        Part::Name => new.push_str(&stem),
+                        Part::Ext => match &ext {
+                            Some(e) => new.push_str(e),
+                            // So `{name}.{ext}` doesn't leave a trailing dot on names without one
+                            None => {
+                                if new.ends_with('.') {
+                                    new.pop();
+                                }
+                            }
+                        },
+                    }
+                }
+
+                Some(new)
+            }
+        }
+    }
+
+    /// Works out new names for every name the pattern applies to, returning the old and new names
+    /// in matching order. The counter only goes up for names that are renamed.
+    pub fn apply_all(&self, names: &[String]) -> (Vec<String>, Vec<String>) {
+        let mut old = Vec::new();
+        let mut new = Vec::new();
+
+        for name in names {
+            if let Some(n) = self.apply(name, old.len() + 1) {
+                old.push(name.clone());
+                new.push(n);
+            }
+        }
+
+        (old, new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsops::TempDir;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    // A directory with a file for each name, containing that name
+    fn scratch(files: &[&str]) -> TempDir {
+        let dir = TempDir::create();
+        for f in files {
+            fs::write(dir.join(f), f).unwrap();
+        }
+
+        dir
+    }
+
+    // Plans and applies renaming `old` to `new` in `dir`
+    fn rename(dir: &Path, old: &[&str], new: &[&str]) -> Result<Vec<Operation>> {
+        let plan = RenamePlan::new(dir.to_path_buf(), &names(old), &names(new))?;
+        let mut operations = Vec::new();
+        plan.apply(&mut operations)?;
+
+        Ok(operations)
+    }
+
+    fn contents(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    // Nothing is left under a temporary name
+    fn no_temps(dir: &Path) -> bool {
+        fs::read_dir(dir).unwrap().all(|e| {
+            !e.unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".thunars")
+        })
+    }
+
+    #[test]
+    fn swap() {
+        let dir = scratch(&["a", "b"]);
+        rename(&dir, &["a", "b"], &["b", "a"]).unwrap();
+
+        assert_eq!(contents(&dir, "a"), "b");
+        assert_eq!(contents(&dir, "b"), "a");
+        assert!(no_temps(&dir));
+    }
+
+    #[test]
+    fn three_cycle() {
+        let dir = scratch(&["a", "b", "c"]);
+        let operations = rename(&dir, &["a", "b", "c"], &["b", "c", "a"]).unwrap();
+
+        assert_eq!(contents(&dir, "b"), "a");
+        assert_eq!(contents(&dir, "c"), "b");
+        assert_eq!(contents(&dir, "a"), "c");
+        assert!(no_temps(&dir));
+        // Each file is renamed to a temporary name and then to its new one
+        assert_eq!(operations.len(), 6);
+    }
+
+    #[test]
+    fn duplicate_targets_are_refused() {
+        let dir = scratch(&["a", "b"]);
+
+        assert!(rename(&dir, &["a", "b"], &["c", "c"]).is_err());
+        assert_eq!(contents(&dir, "a"), "a");
+        assert_eq!(contents(&dir, "b"), "b");
+        assert!(!dir.join("c").exists());
+    }
+
+    #[test]
+    fn existing_targets_are_refused() {
+        let dir = scratch(&["a", "b", "other"]);
+
+        assert!(rename(&dir, &["a", "b"], &["other", "c"]).is_err());
+        assert_eq!(contents(&dir, "other"), "other");
+        assert_eq!(contents(&dir, "a"), "a");
+    }
+
+    #[test]
+    fn unchanged_names_are_left_out() {
+        let dir = scratch(&["a", "b"]);
+        let plan =
+            RenamePlan::new(dir.to_path_buf(), &names(&["a", "b"]), &names(&["a", "c"])).unwrap();
+        assert_eq!(plan.renames(), [("b".to_string(), "c".to_string())]);
+
+        let plan =
+            RenamePlan::new(dir.to_path_buf(), &names(&["a", "b"]), &names(&["a", "b"])).unwrap();
+        assert!(plan.is_empty());
+        assert!(rename(&dir, &["a", "b"], &["a", "b"]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_names_are_refused() {
+        let dir = scratch(&["a"]);
+
+        for new in ["", ".", "..", "sub/a"] {
+            assert!(rename(&dir, &["a"], &[new]).is_err());
+        }
+        assert!(rename(&dir, &["a"], &[]).is_err());
+        assert_eq!(contents(&dir, "a"), "a");
+    }
+
+    #[test]
+    fn failure_puts_everything_back() {
+        let dir = scratch(&["a", "b"]);
+        let plan =
+            RenamePlan::new(dir.to_path_buf(), &names(&["a", "b"]), &names(&["x", "y"])).unwrap();
+
+        // Files can't be renamed over a directory that isn't empty, so the last step fails
+        fs::create_dir(dir.join("y")).unwrap();
+        fs::write(dir.join("y/inner"), "").unwrap();
+
+        let mut operations = Vec::new();
+        assert!(plan.apply(&mut operations).is_err());
+        assert!(operations.is_empty());
+        assert_eq!(contents(&dir, "a"), "a");
+        assert_eq!(contents(&dir, "b"), "b");
+        assert!(!dir.join("x").exists());
+        assert!(no_temps(&dir));
+    }
+
+    #[test]
+    fn taken_temporary_names_are_refused() {
+        let dir = scratch(&["a", "b"]);
+        let plan =
+            RenamePlan::new(dir.to_path_buf(), &names(&["a", "b"]), &names(&["b", "a"])).unwrap();
+        fs::write(dir.join(format!(".thunars-rename-{}-1", process::id())), "").unwrap();
+
+        let mut operations = Vec::new();
+        assert!(plan.apply(&mut operations).is_err());
+        assert!(operations.is_empty());
+        assert_eq!(contents(&dir, "a"), "a");
+        assert_eq!(contents(&dir, "b"), "b");
+    }
+
+    // The new names `pattern` gives `files`, or `None` for ones it leaves alone
+    fn pattern(pattern: &str, files: &[&str]) -> Vec<Option<String>> {
+        let pattern = RenamePattern::parse(pattern).unwrap();
+        files.iter().map(|f| pattern.apply(f, 1)).collect()
+    }
+
+    #[test]
+    fn substitute() {
+        assert_eq!(
+            pattern("s/o/0/", &["foo.txt", "bar"]),
+            [Some("f0o.txt".to_string()), None]
+        );
+        assert_eq!(
+            pattern("s/o/0/g", &["foo.txt"]),
+            [Some("f00.txt".to_string())]
+        );
+        assert_eq!(
+            pattern("s/FOO/bar/i", &["foo.txt"]),
+            [Some("bar.txt".to_string())]
+        );
+        assert_eq!(pattern("s/FOO/bar/", &["foo.txt"]), [None]);
+        assert_eq!(
+            pattern(r"s/(\w+)\.(\w+)/$2.$1/", &["foo.txt"]),
+            [Some("txt.foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn substitute_escaped_slashes() {
+        assert_eq!(pattern(r"s/-/\//", &["a-b"]), [Some("a/b".to_string())]);
+        assert_eq!(pattern(r"s/\//-/", &["a/b"]), [Some("a-b".to_string())]);
+        // Other escapes are passed through to the regex
+        assert_eq!(
+            pattern(r"s/\./-/g", &["a.b.c"]),
+            [Some("a-b-c".to_string())]
+        );
+    }
+
+    #[test]
+    fn bad_substitutions_are_refused() {
+        for bad in ["s/(/x/", "s/a/b/x", "s/a", "s/a/b/g/extra"] {
+            assert!(RenamePattern::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn template() {
+        let pattern = RenamePattern::parse("{n:03}-{name}.{ext}").unwrap();
+        let (old, new) = pattern.apply_all(&names(&["b.txt", "a.tar.gz", "README", ".bashrc"]));
+
+        assert_eq!(old, names(&["b.txt", "a.tar.gz", "README", ".bashrc"]));
+        assert_eq!(
+            new,
+            names(&["001-b.txt", "002-a.tar.gz", "003-README", "004-.bashrc"])
+        );
+    }
+
+    #[test]
+    fn template_counter_and_braces() {
+        assert_eq!(pattern("{n}", &["a"]), [Some("1".to_string())]);
+        assert_eq!(pattern("{{{name}}}", &["a.txt"]), [Some("{a}".to_string())]);
+        assert_eq!(pattern("{ext}", &["a"]), [Some("".to_string())]);
+
+        for bad in ["{count}", "{n:x}", "a}b"] {
+            assert!(RenamePattern::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn unmatched_names_are_left_out() {
+        let pattern = RenamePattern::parse("s/b/B/").unwrap();
+        let (old, new) = pattern.apply_all(&names(&["a", "b", "c", "ab"]));
+
+        assert_eq!(old, names(&["b", "ab"]));
+        assert_eq!(new, names(&["B", "aB"]));
+    }
+}
@@ -0,0 +1,608 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ignore::{types::TypesBuilder, WalkBuilder};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    config::{FinderBackend, FinderFilter},
+    fuzzy::{self, Match},
+    Result,
+};
+
+// Paths found by the walker are handed over in batches, so the lock isn't taken for every one
+const WALK_BATCH: usize = 256;
+
+// Filtering stops for the frame once it has taken this long, so typing never holds up drawing
+const TICK_BUDGET: Duration = Duration::from_millis(8);
+const TICK_CHUNK: usize = 256;
+
+// Files are treated as binary, and left out of content searches, if there's a NUL byte this early
+const BINARY_CHECK_LEN: u64 = 8192;
+
+// Bigger files are left out of content searches too, since they'd be read into memory whole
+const MAX_GREP_LEN: u64 = 16 * 1024 * 1024;
+
+/// Which entries the finder's walk includes
+#[derive(Clone)]
+pub struct Filters {
+    pub files_only: bool,
+    pub dirs_only: bool,
+    pub hidden: bool,     // include hidden entries
+    pub git_ignore: bool, // leave out entries ignored by `.gitignore` or `.ignore` files
+    pub follow_links: bool,
+    pub max_depth: Option<usize>,
+    pub file_type: Option<String>, // a file type known to ripgrep, like `rust` or `py`
+    pub types: Vec<String>,        // the file types to cycle through
+}
+
+impl Filters {
+    pub fn toggle(&mut self, filter: FinderFilter) {
+        match filter {
+            FinderFilter::FilesOnly => {
+                self.files_only = !self.files_only;
+                self.dirs_only = false;
+            }
+            FinderFilter::DirsOnly => {
+                self.dirs_only = !self.dirs_only;
+                self.files_only = false;
+            }
+            FinderFilter::Hidden => self.hidden = !self.hidden,
+            FinderFilter::GitIgnore => self.git_ignore = !self.git_ignore,
+            FinderFilter::FollowLinks => self.follow_links = !self.follow_links,
+            // No limit, then 1 to 5 levels deep
+            FinderFilter::MaxDepth => {
+                self.max_depth = match self.max_depth {
+                    None => Some(1),
+                    Some(d) if d < 5 => Some(d + 1),
+                    Some(_) => None,
+                }
+            }
+            // All types, then each of `types` in turn
+            FinderFilter::FileType => {
+                let next = match &self.file_type {
+                    None => 0,
+                    Some(t) => self.types.iter().position(|c| c == t).map_or(0, |i| i + 1),
+                };
+                self.file_type = self.types.get(next).cloned();
+            }
+        }
+    }
+
+    /// Lists the filters that change what's shown, like `files only · depth 2 · rust`
+    pub fn describe(&self) -> String {
+        let mut active = Vec::new();
+
+        if self.files_only {
+            active.push("files only".to_string());
+        }
+        if self.dirs_only {
+            active.push("dirs only".to_string());
+        }
+        if self.hidden {
+            active.push("hidden".to_string());
+        }
+        if !self.git_ignore {
+            active.push("ignored".to_string());
+        }
+        if self.follow_links {
+            active.push("following links".to_string());
+        }
+        if let Some(d) = self.max_depth {
+            active.push(format!("depth {}", d));
+        }
+        if let Some(t) = &self.file_type {
+            active.push(t.clone());
+        }
+
+        active.join(" · ")
+    }
+
+    // A walk of `root` with these filters applied, shared by the finder and content search
+    fn walk_builder(&self, root: &Path) -> Result<WalkBuilder> {
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .hidden(!self.hidden)
+            .ignore(self.git_ignore)
+            .git_ignore(self.git_ignore)
+            .git_global(self.git_ignore)
+            .git_exclude(self.git_ignore)
+            .follow_links(self.follow_links)
+            .max_depth(self.max_depth);
+
+        if let Some(t) = &self.file_type {
+            let mut types = TypesBuilder::new();
+            types.add_defaults();
+            types.select(t);
+            builder.types(types.build()?);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Walks a directory tree on a background thread, collecting paths (relative to the root) as
+/// they're found. The walk is abandoned when the walker is dropped.
+pub struct Walker {
+    paths: Arc<Mutex<Vec<String>>>,
+    done: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Walker {
+    pub fn start(root: PathBuf, filters: &Filters) -> Result<Self> {
+        let builder = filters.walk_builder(&root)?;
+
+        // Type filters only apply to files, so directories would otherwise all be listed
+        let files_only = filters.files_only || filters.file_type.is_some();
+        let dirs_only = filters.dirs_only;
+
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let done = Arc::new(AtomicBool::new(false));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let (shared, finished, stop) = (paths.clone(), done.clone(), cancelled.clone());
+        thread::spawn(move || {
+            let mut batch = Vec::new();
+
+            // Entries we can't read, like directories without permission, are skipped
+            for entry in builder.build().flatten() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                if (files_only && is_dir) || (dirs_only && !is_dir) {
+                    continue;
+                }
+
+                let path = entry
+                    .path()
+                    .strip_prefix(&root)
+                    .expect("Found file not in curr directory")
+                    .to_string_lossy()
+                    .to_string();
+
+                // Walk has directory itself as member, filter that out
+                if !path.is_empty() {
+                    batch.push(path);
+                }
+
+                if batch.len() >= WALK_BATCH {
+                    shared.lock().unwrap().append(&mut batch);
+                }
+            }
+
+            shared.lock().unwrap().append(&mut batch);
+            finished.store(true, Ordering::Relaxed);
+        });
+
+        Ok(Self {
+            paths,
+            done,
+            cancelled,
+        })
+    }
+
+    /// How many paths have been found so far
+    pub fn len(&self) -> usize {
+        self.paths.lock().unwrap().len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// Copies out the paths in `range`, which must have been found already
+    pub fn paths(&self, range: Range<usize>) -> Vec<String> {
+        self.paths.lock().unwrap()[range].to_vec()
+    }
+}
+
+impl Drop for Walker {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Filters the paths found by a `Walker` against a query while the walk is still going. The
+/// native matcher works through the paths a chunk at a time on every `tick`, while fzf is run
+/// on a background thread.
+pub struct Search {
+    walker: Walker,
+    backend: FinderBackend,
+    query: String,
+    limit: usize,
+    scanned: usize, // how many of the walker's paths have been matched against the query
+    matches: Vec<Match>,
+    fzf: Option<Receiver<io::Result<Vec<Match>>>>, // results of the running fzf process
+}
+
+impl Search {
+    pub fn new(walker: Walker, backend: FinderBackend) -> Self {
+        Self {
+            walker,
+            backend,
+            query: String::new(),
+            limit: 0,
+            scanned: 0,
+            matches: Vec::new(),
+            fzf: None,
+        }
+    }
+
+    /// Starts filtering for `query` from scratch, unless it's unchanged
+    pub fn set_query(&mut self, query: &str, limit: usize) {
+        if query != self.query || limit != self.limit {
+            self.query = query.to_string();
+            self.limit = limit;
+            self.scanned = 0;
+            self.matches.clear();
+            self.fzf = None; // whatever's running is for an old query
+        }
+    }
+
+    /// Does a bit more filtering, returning true if the matches changed
+    pub fn tick(&mut self) -> Result<bool> {
+        match self.backend {
+            FinderBackend::Native => Ok(self.tick_native()),
+            FinderBackend::Fzf if self.query.is_empty() => Ok(self.tick_native()),
+            FinderBackend::Fzf => self.tick_fzf(),
+        }
+    }
+
+    fn tick_native(&mut self) -> bool {
+        let start = Instant::now();
+        let found = self.walker.len();
+        let changed = self.scanned < found;
+
+        while self.scanned < found && start.elapsed() < TICK_BUDGET {
+            let end = found.min(self.scanned + TICK_CHUNK);
+            let paths = self.walker.paths(self.scanned..end);
+            self.scanned = end;
+
+            if self.query.is_empty() {
+                // Unfiltered results stay in the order they were found
+                let room = self.limit.saturating_sub(self.matches.len());
+                self.matches
+                    .extend(paths.into_iter().take(room).map(Match::plain));
+            } else {
+                self.matches.extend(
+                    paths
+                        .iter()
+                        .filter_map(|p| fuzzy::fuzzy_match(&self.query, p)),
+                );
+                fuzzy::rank(&mut self.matches, self.limit);
+            }
+        }
+
+        changed
+    }
+
+    // fzf needs every path up front, so it's rerun over everything found so far whenever the
+    // previous run finishes and more paths have turned up
+    fn tick_fzf(&mut self) -> Result<bool> {
+        let mut changed = false;
+
+        if let Some(rx) = &self.fzf {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.matches = result?;
+                    self.fzf = None;
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => self.fzf = None,
+            }
+        }
+
+        let found = self.walker.len();
+        if self.scanned < found {
+            let paths = self.walker.paths(0..found);
+            let (query, limit) = (self.query.clone(), self.limit);
+            let (tx, rx) = mpsc::channel();
+
+            thread::spawn(move || {
+                // Nobody is listening if the query changed in the meantime
+                let _ = tx.send(fzf(&query, paths, limit));
+            });
+
+            self.scanned = found;
+            self.fzf = Some(rx);
+        }
+
+        Ok(changed)
+    }
+
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    /// Describes how far along the walk is, like `scanning… 1204 files`
+    pub fn describe(&self) -> String {
+        if self.walker.is_done() {
+            format!("{} files", self.walker.len())
+        } else {
+            format!("scanning… {} files", self.walker.len())
+        }
+    }
+}
+
+/// A line found by a content search
+#[derive(Clone)]
+pub struct GrepHit {
+    path: String,        // relative to the directory searched
+    line: usize,         // starting from 1
+    text: String,        // the line, without leading whitespace
+    range: Range<usize>, // where the first match is in `text`, in bytes
+}
+
+impl GrepHit {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Formats the hit like `path:line: text`, highlighting the match
+    pub fn to_match(&self) -> Match {
+        let prefix = format!("{}:{}: ", self.path, self.line);
+        let start = prefix.chars().count() + self.text[..self.range.start].chars().count();
+        let len = self.text[self.range.clone()].chars().count();
+
+        Match::highlighted(prefix + &self.text, (start..start + len).collect())
+    }
+}
+
+// A content search running on a background thread, abandoned when dropped
+struct GrepRun {
+    hits: Arc<Mutex<Vec<GrepHit>>>,
+    searched: Arc<AtomicUsize>, // how many files have been searched
+    done: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl GrepRun {
+    fn start(builder: WalkBuilder, root: PathBuf, regex: Regex, limit: usize) -> Self {
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let searched = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let (shared, count, finished, stop) = (
+            hits.clone(),
+            searched.clone(),
+            done.clone(),
+            cancelled.clone(),
+        );
+        thread::spawn(move || {
+            for entry in builder.build().flatten() {
+                if stop.load(Ordering::Relaxed) || shared.lock().unwrap().len() >= limit {
+                    break;
+                }
+
+                if !entry.file_type().is_some_and(|t| t.is_file()) {
+                    continue;
+                }
+
+                let path = entry
+                    .path()
+                    .strip_prefix(&root)
+                    .expect("Found file not in curr directory")
+                    .to_string_lossy()
+                    .to_string();
+
+                // Files we can't read are skipped, as are binary files
+                if let Some(contents) = read_text(entry.path()) {
+                    let found = grep(&regex, &path, &contents, limit);
+                    let mut hits = shared.lock().unwrap();
+                    let room = limit.saturating_sub(hits.len());
+                    hits.extend(found.into_iter().take(room));
+                }
+
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+
+            finished.store(true, Ordering::Relaxed);
+        });
+
+        Self {
+            hits,
+            searched,
+            done,
+            cancelled,
+        }
+    }
+}
+
+impl Drop for GrepRun {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+// Reads `path` if it's a text file that isn't too big. Only the start is read to tell.
+fn read_text(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_GREP_LEN {
+        return None;
+    }
+
+    let mut contents = Vec::new();
+    (&file)
+        .take(BINARY_CHECK_LEN)
+        .read_to_end(&mut contents)
+        .ok()?;
+    if contents.contains(&0) {
+        return None;
+    }
+
+    // The file may have grown since its size was checked
+    file.take(MAX_GREP_LEN - BINARY_CHECK_LEN)
+        .read_to_end(&mut contents)
+        .ok()?;
+
+    String::from_utf8(contents).ok()
+}
+
+fn grep(regex: &Regex, path: &str, contents: &str, limit: usize) -> Vec<GrepHit> {
+    // Most files won't match at all, which is quicker to check for in one go
+    if !regex.is_match(contents) {
+        return Vec::new();
+    }
+
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let text = line.trim_start();
+            regex.find(text).map(|m| GrepHit {
+                path: path.to_string(),
+                line: i + 1,
+                text: text.to_string(),
+                range: m.range(),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Searches the contents of the files under a directory for a regex, restarting whenever the
+/// query changes. Like the finder's fuzzy matching, the search ignores case unless the query has
+/// an uppercase letter.
+pub struct Grep {
+    root: PathBuf,
+    filters: Filters,
+    query: String,
+    limit: usize,
+    run: Option<GrepRun>,
+    error: Option<String>, // why the query couldn't be searched for, like an invalid regex
+    seen: Option<usize>,   // how many hits `tick` has reported, `None` after a new query
+}
+
+impl Grep {
+    pub fn new(root: PathBuf, filters: Filters) -> Self {
+        Self {
+            root,
+            filters,
+            query: String::new(),
+            limit: 0,
+            run: None,
+            error: None,
+            seen: None,
+        }
+    }
+
+    /// Starts searching for `query`, unless it's unchanged
+    pub fn set_query(&mut self, query: &str, limit: usize) {
+        if query == self.query && limit == self.limit {
+            return;
+        }
+
+        self.query = query.to_string();
+        self.limit = limit;
+        self.run = None;
+        self.error = None;
+        self.seen = None;
+
+        if query.is_empty() {
+            return;
+        }
+
+        match self.start(query, limit) {
+            Ok(run) => self.run = Some(run),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn start(&self, query: &str, limit: usize) -> Result<GrepRun> {
+        let regex = RegexBuilder::new(query)
+            .case_insensitive(!query.chars().any(char::is_uppercase))
+            .build()?;
+        let builder = self.filters.walk_builder(&self.root)?;
+
+        Ok(GrepRun::start(builder, self.root.clone(), regex, limit))
+    }
+
+    /// Returns true if there are new hits, or the old ones were cleared by a new query
+    pub fn tick(&mut self) -> bool {
+        let found = self
+            .run
+            .as_ref()
+            .map_or(0, |r| r.hits.lock().unwrap().len());
+        let changed = self.seen != Some(found);
+        self.seen = Some(found);
+
+        changed
+    }
+
+    pub fn hits(&self) -> Vec<GrepHit> {
+        self.run
+            .as_ref()
+            .map(|r| r.hits.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// Describes how the search is going, like `searching… 120 files, 4 matches`
+    pub fn describe(&self) -> String {
+        if let Some(e) = &self.error {
+            return e.clone();
+        }
+
+        let Some(run) = &self.run else {
+            return String::new();
+        };
+
+        let searched = run.searched.load(Ordering::Relaxed);
+        let hits = run.hits.lock().unwrap().len();
+
+        if !run.done.load(Ordering::Relaxed) {
+            format!("searching… {} files, {} matches", searched, hits)
+        } else if hits >= self.limit {
+            format!("first {} matches", hits)
+        } else {
+            format!("{} matches in {} files", hits, searched)
+        }
+    }
+}
+
+fn fzf(query: &str, paths: Vec<String>, limit: usize) -> io::Result<Vec<Match>> {
+    let mut command = Command::new("fzf")
+        .arg("-f")
+        .arg(query)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    {
+        let mut stdin = command
+            .stdin
+            .take()
+            .expect("Unable to access stdin for fzf");
+        for p in paths {
+            stdin.write_fmt(format_args!("{}\n", p))?;
+        }
+    }
+
+    // fzf doesn't say which characters matched, so our own matcher picks them out. Its extended
+    // search syntax can match things ours doesn't, which are left unhighlighted.
+    Ok(String::from_utf8_lossy(&command.wait_with_output()?.stdout)
+        .lines()
+        .take(limit)
+        .map(|l| fuzzy::fuzzy_match(query, l).unwrap_or_else(|| Match::plain(l.into())))
+        .collect())
+}
@@ -0,0 +1,340 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{fsops, xdg_dir, Result};
+
+/// An item in the trash, described by its `.trashinfo` file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    name: String, // name of the item inside the `files` directory
+    original: PathBuf,
+    deleted: String, // as written in the info file, e.g. `2024-08-30T17:05:01`
+}
+
+impl TrashEntry {
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+
+    pub fn deleted(&self) -> &str {
+        &self.deleted
+    }
+}
+
+/// The home trash directory, as described by the FreeDesktop trash specification
+pub struct Trash {
+    files: PathBuf,
+    info: PathBuf,
+}
+
+impl Trash {
+    /// Opens the trash under `$XDG_DATA_HOME`, which defaults to `~/.local/share`
+    pub fn init() -> Result<Self> {
+        Self::at(xdg_dir("XDG_DATA_HOME", ".local/share")?.join("Trash"))
+    }
+
+    /// Opens the trash rooted at `root`, creating it if needed
+    pub fn at(root: PathBuf) -> Result<Self> {
+        let files = root.join("files");
+        let info = root.join("info");
+
+        fs::create_dir_all(&files)?;
+        fs::create_dir_all(&info)?;
+
+        Ok(Self { files, info })
+    }
+
+    /// Moves `path` into the trash, returning the new entry
+    pub fn trash(&self, path: &Path) -> Result<TrashEntry> {
+        let original = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            env::current_dir()?.join(path)
+        };
+        let base = original
+            .file_name()
+            .expect("Trying to trash root directory")
+            .to_string_lossy()
+            .to_string();
+        let deleted = Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+
+        // Creating the info file first reserves the name, as the spec requires
+        let (name, mut info_file) = (0..)
+            .map(|i| match i {
+                0 => base.clone(),
+                i => format!("{}.{}", base, i),
+            })
+            .find_map(|name| {
+                if fs::symlink_metadata(self.files.join(&name)).is_ok() {
+                    return None;
+                }
+
+                match OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(self.info_path(&name))
+                {
+                    Ok(f) => Some(Ok((name, f))),
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => None,
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .expect("Ran out of trash names")?;
+
+        let contents = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&original),
+            deleted
+        );
+
+        let result = info_file
+            .write_all(contents.as_bytes())
+            .map_err(Into::into)
+            .and_then(|_| fsops::move_path(&original, &self.files.join(&name)));
+
+        if let Err(e) = result {
+            fs::remove_file(self.info_path(&name))?;
+            return Err(e);
+        }
+
+        Ok(TrashEntry {
+            name,
+            original,
+            deleted,
+        })
+    }
+
+    /// Lists everything in the trash, most recently deleted first
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        let mut entries = Vec::new();
+
+        for f in fs::read_dir(&self.info)? {
+            let path = f?.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_suffix(".trashinfo"))
+            else {
+                continue;
+            };
+
+            // Entries we can't make sense of are left alone
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            if let Some(entry) = parse_info(name, &contents) {
+                entries.push(entry);
+            }
+        }
+
+        entries.sort_by(|a, b| b.deleted.cmp(&a.deleted));
+
+        Ok(entries)
+    }
+
+    /// Moves `entry` back to where it was deleted from
+    pub fn restore(&self, entry: &TrashEntry) -> Result<()> {
+        fsops::ensure_free(&entry.original)?;
+
+        if let Some(parent) = entry.original.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fsops::move_path(&self.files.join(&entry.name), &entry.original)?;
+        fs::remove_file(self.info_path(&entry.name))?;
+
+        Ok(())
+    }
+
+    /// Permanently deletes `entry`
+    pub fn purge(&self, entry: &TrashEntry) -> Result<()> {
+        let file = self.files.join(&entry.name);
+        if fs::symlink_metadata(&file).is_ok() {
+            fsops::remove_path(&file)?;
+        }
+
+        fs::remove_file(self.info_path(&entry.name))?;
+
+        Ok(())
+    }
+
+    fn info_path(&self, name: &str) -> PathBuf {
+        self.info.join(format!("{}.trashinfo", name))
+    }
+}
+
+fn parse_info(name: &str, contents: &str) -> Option<TrashEntry> {
+    let mut lines = contents.lines().map(str::trim);
+    if lines.next()? != "[Trash Info]" {
+        return None;
+    }
+
+    let mut original = None;
+    let mut deleted = None;
+    for line in lines {
+        if let Some(p) = line.strip_prefix("Path=") {
+            original = Some(decode_path(p)?);
+        } else if let Some(d) = line.strip_prefix("DeletionDate=") {
+            deleted = Some(d.to_string());
+        }
+    }
+
+    Some(TrashEntry {
+        name: name.to_string(),
+        original: original?,
+        deleted: deleted?,
+    })
+}
+
+// Paths are stored percent-encoded, like in URLs
+fn encode_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn decode_path(s: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::new();
+    let mut iter = s.bytes();
+
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsops::TempDir;
+
+    // A trash in its own temporary directory, along with a directory to delete things from
+    fn scratch() -> (TempDir, Trash) {
+        let root = TempDir::create();
+        let trash = Trash::at(root.join("Trash")).unwrap();
+        fs::create_dir(root.join("home")).unwrap();
+
+        (root, trash)
+    }
+
+    #[test]
+    fn trash_and_restore() {
+        let (root, trash) = scratch();
+        let file = root.join("home/notes.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let entry = trash.trash(&file).unwrap();
+        assert!(!file.exists());
+        assert_eq!(entry.original(), file);
+        assert_eq!(
+            fs::read_to_string(root.join("Trash/files/notes.txt")).unwrap(),
+            "hello"
+        );
+
+        let listed = trash.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "notes.txt");
+        assert_eq!(listed[0].original(), file);
+        assert_eq!(listed[0].deleted(), entry.deleted());
+
+        trash.restore(&listed[0]).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "hello");
+        assert!(trash.list().unwrap().is_empty());
+        assert!(!root.join("Trash/info/notes.txt.trashinfo").exists());
+    }
+
+    #[test]
+    fn colliding_names_are_numbered() {
+        let (root, trash) = scratch();
+        let first = root.join("home/a.txt");
+        let second = root.join("home/sub/a.txt");
+        fs::create_dir(root.join("home/sub")).unwrap();
+        fs::write(&first, "first").unwrap();
+        fs::write(&second, "second").unwrap();
+
+        let a = trash.trash(&first).unwrap();
+        let b = trash.trash(&second).unwrap();
+        assert_eq!(a.name, "a.txt");
+        assert_eq!(b.name, "a.txt.1");
+
+        let mut listed = trash.list().unwrap();
+        listed.sort_by(|x, y| x.name.cmp(&y.name));
+        assert_eq!(listed[0].original(), first);
+        assert_eq!(listed[1].original(), second);
+
+        trash.restore(&listed[1]).unwrap();
+        trash.restore(&listed[0]).unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second");
+    }
+
+    #[test]
+    fn restore_refuses_to_overwrite() {
+        let (root, trash) = scratch();
+        let file = root.join("home/a.txt");
+        fs::write(&file, "old").unwrap();
+
+        let entry = trash.trash(&file).unwrap();
+        fs::write(&file, "new").unwrap();
+
+        assert!(trash.restore(&entry).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert_eq!(trash.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn purge_removes_file_and_info() {
+        let (root, trash) = scratch();
+        let dir = root.join("home/dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("inner"), "x").unwrap();
+
+        let entry = trash.trash(&dir).unwrap();
+        trash.purge(&entry).unwrap();
+
+        assert!(trash.list().unwrap().is_empty());
+        assert!(!root.join("Trash/files/dir").exists());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        for path in ["/plain/path.txt", "/with space/100%/ümlaut", "/a\nb/#?&="] {
+            let encoded = encode_path(Path::new(path));
+            assert!(!encoded.contains([' ', '\n']));
+            assert_eq!(decode_path(&encoded).unwrap(), Path::new(path));
+        }
+
+        assert_eq!(encode_path(Path::new("/a b/%")), "/a%20b/%25");
+        assert!(decode_path("/bad%zz").is_none());
+        assert!(decode_path("/cut%2").is_none());
+    }
+
+    #[test]
+    fn unreadable_info_is_skipped() {
+        let (root, trash) = scratch();
+        fs::write(root.join("Trash/info/junk.trashinfo"), "not an info file").unwrap();
+        fs::write(root.join("Trash/info/binary.trashinfo"), [0xff, 0xfe, 0x00]).unwrap();
+
+        assert!(trash.list().unwrap().is_empty());
+    }
+}
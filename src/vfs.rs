@@ -0,0 +1,690 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, Metadata},
+    io::{self, ErrorKind, Read},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{fsops, Result};
+
+// Longer extensions come first, so `.tar.gz` isn't taken for `.tar`
+const EXTENSIONS: [(&str, Format); 6] = [
+    (".tar.gz", Format::TarGz),
+    (".tar.zst", Format::TarZst),
+    (".tgz", Format::TarGz),
+    (".tzst", Format::TarZst),
+    (".tar", Format::Tar),
+    (".zip", Format::Zip),
+];
+
+/// The kinds of archive that can be browsed like directories
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl Format {
+    /// Detects the format from the file name
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+        EXTENSIONS
+            .iter()
+            .find(|(ext, _)| name.ends_with(ext))
+            .map(|(_, format)| *format)
+    }
+}
+
+/// Returns where an archive is extracted to, next to it and named after it without the extension
+pub fn extract_dir(archive: &Path) -> PathBuf {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let lower = name.to_lowercase();
+
+    let stem = EXTENSIONS
+        .iter()
+        .find(|(ext, _)| lower.ends_with(ext) && lower.len() > ext.len())
+        .map_or(&*name, |(ext, _)| &name[..name.len() - ext.len()]);
+
+    archive.with_file_name(stem)
+}
+
+/// Counts the entries written while an archive is created or extracted, and lets it be cancelled
+/// from another thread
+#[derive(Clone, Default)]
+pub struct Progress {
+    done: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress {
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn start(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    // Called before each entry, failing once the user has cancelled
+    fn advance(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(ErrorKind::Interrupted, "Cancelled").into());
+        }
+
+        self.done.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Opens a tar, decompressing it as it's read
+pub fn open_tar(path: &Path, format: Format) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = match format {
+        Format::TarGz => Box::new(GzDecoder::new(file)),
+        Format::TarZst => Box::new(zstd::Decoder::new(file)?),
+        Format::Tar | Format::Zip => Box::new(file),
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+/// An entry in an archive
+pub struct Entry {
+    pub path: PathBuf, // relative to the root of the archive
+    pub dir: bool,
+}
+
+/// The error for a file that was expected to be an archive
+pub fn not_archive() -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, "Not an archive")
+}
+
+/// The error for anything that would change the contents of an archive
+pub fn read_only() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, "Archives are read-only")
+}
+
+// Archives made elsewhere can have entries like `../../.bashrc` or `/etc/passwd`, which would
+// escape the directory they're extracted to ("zip slip"). Those are left out.
+fn enclosed(path: &Path) -> Option<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => enclosed.push(c),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+
+    (!enclosed.as_os_str().is_empty()).then_some(enclosed)
+}
+
+/// Splits a path like `/a/b.zip/c/d` into the archive and the path inside it, or returns `None`
+/// if it isn't in an archive. The path inside is empty for the archive itself.
+pub fn split(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    path.ancestors()
+        .find(|a| Format::detect(a).is_some() && a.is_file())
+        .map(|a| {
+            let inner = path.strip_prefix(a).expect("Ancestor isn't a prefix");
+            (a.to_path_buf(), inner.to_path_buf())
+        })
+}
+
+/// Returns true if `path` is somewhere inside an archive, rather than being the archive itself
+pub fn inside(path: &Path) -> bool {
+    split(path).is_some_and(|(_, inner)| !inner.as_os_str().is_empty())
+}
+
+/// Returns true if `path` can be browsed as a directory, meaning it's an archive or a directory in
+/// one
+pub fn is_dir(path: &Path) -> bool {
+    let Some((archive, inner)) = split(path) else {
+        return false;
+    };
+
+    inner.as_os_str().is_empty()
+        || entries(&archive).is_ok_and(|entries| {
+            entries
+                .iter()
+                .any(|e| (e.path == inner && e.dir) || e.path.parent() == Some(inner.as_path()))
+        })
+}
+
+/// Lists every entry in an archive
+pub fn entries(archive: &Path) -> Result<Vec<Entry>> {
+    let format = Format::detect(archive).ok_or_else(not_archive)?;
+
+    let mut entries = Vec::new();
+    if format == Format::Zip {
+        let mut zip = ZipArchive::new(File::open(archive)?)?;
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i)?;
+            if entry.is_symlink() {
+                continue;
+            }
+
+            if let Some(path) = entry.enclosed_name().as_deref().and_then(enclosed) {
+                entries.push(Entry {
+                    path,
+                    dir: entry.is_dir(),
+                });
+            }
+        }
+    } else {
+        for entry in open_tar(archive, format)?.entries()? {
+            let entry = entry?;
+            let kind = entry.header().entry_type();
+
+            // Links and devices aren't browsable
+            if !kind.is_file() && !kind.is_dir() {
+                continue;
+            }
+
+            if let Some(path) = enclosed(&entry.path()?) {
+                entries.push(Entry {
+                    path,
+                    dir: kind.is_dir(),
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Lists the directories and files directly inside `inner`, sorted by name. Directories that
+/// only show up as part of a longer path are included.
+pub fn read_dir(archive: &Path, inner: &Path) -> Result<(Vec<String>, Vec<String>)> {
+    let mut dirs = BTreeSet::new();
+    let mut files = BTreeSet::new();
+
+    for entry in entries(archive)? {
+        let Ok(rel) = entry.path.strip_prefix(inner) else {
+            continue;
+        };
+
+        let mut components = rel.components();
+        let Some(name) = components.next() else {
+            continue;
+        };
+        let name = name.as_os_str().to_string_lossy().to_string();
+
+        if entry.dir || components.next().is_some() {
+            dirs.insert(name);
+        } else {
+            files.insert(name);
+        }
+    }
+
+    Ok((dirs.into_iter().collect(), files.into_iter().collect()))
+}
+
+/// Extracts `inner` from an archive to `dst`, along with everything under it if it's a directory
+pub fn extract(archive: &Path, inner: &Path, dst: &Path) -> Result<()> {
+    extract_with(archive, inner, dst, || Ok(()))
+}
+
+/// Extracts a whole archive into a new directory `dst`. Entries that would end up outside of it,
+/// like `../../.bashrc`, are skipped. Nothing is left behind if it fails or is cancelled.
+pub fn extract_all(archive: &Path, dst: &Path, progress: &Progress) -> Result<()> {
+    // The archive is read twice so there's a total to show, since tars don't have an index
+    let total = entries(archive)?.len();
+    progress.start(total);
+
+    fs::create_dir(dst)?;
+    if total == 0 {
+        return Ok(());
+    }
+
+    let result = extract_with(archive, Path::new(""), dst, || progress.advance());
+    if result.is_err() {
+        let _ = fsops::remove_path(dst);
+    }
+
+    result
+}
+
+// Calls `on_entry` before writing each entry, stopping if it fails
+fn extract_with(
+    archive: &Path,
+    inner: &Path,
+    dst: &Path,
+    mut on_entry: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let format = Format::detect(archive).ok_or_else(not_archive)?;
+
+    let target = |path: &Path| {
+        path.strip_prefix(inner).ok().map(|rel| {
+            // Joining an empty path would add a trailing slash
+            if rel.as_os_str().is_empty() {
+                dst.to_path_buf()
+            } else {
+                dst.join(rel)
+            }
+        })
+    };
+
+    let mut found = false;
+    if format == Format::Zip {
+        let mut zip = ZipArchive::new(File::open(archive)?)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i)?;
+            if entry.is_symlink() {
+                continue;
+            }
+
+            let Some(path) = entry.enclosed_name().as_deref().and_then(enclosed) else {
+                continue;
+            };
+            let Some(to) = target(&path) else {
+                continue;
+            };
+
+            found = true;
+            on_entry()?;
+            let (dir, mode) = (entry.is_dir(), entry.unix_mode());
+            write_entry(&mut entry, &to, dir, mode)?;
+        }
+    } else {
+        for entry in open_tar(archive, format)?.entries()? {
+            let mut entry = entry?;
+            let kind = entry.header().entry_type();
+            if !kind.is_file() && !kind.is_dir() {
+                continue;
+            }
+
+            let Some(to) = enclosed(&entry.path()?).and_then(|p| target(&p)) else {
+                continue;
+            };
+
+            found = true;
+            on_entry()?;
+            let mode = entry.header().mode().ok();
+            write_entry(&mut entry, &to, kind.is_dir(), mode)?;
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(io::Error::new(ErrorKind::NotFound, "Entry not found in archive").into())
+    }
+}
+
+fn write_entry(reader: &mut impl Read, to: &Path, dir: bool, mode: Option<u32>) -> Result<()> {
+    if dir {
+        fs::create_dir_all(to)?;
+    } else {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(reader, &mut File::create(to)?)?;
+    }
+
+    // Directories keep the default permissions so that a read-only one can still be extracted
+    // into
+    if let (false, Some(mode)) = (dir, mode) {
+        set_mode(to, mode)?;
+    }
+
+    Ok(())
+}
+
+// Only the permission bits are kept, not setuid and the like
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+
+    Ok(())
+}
+
+// Other platforms don't have Unix modes, so files keep the default permissions
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+// The Unix mode stored for an entry when compressing
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &Metadata) -> u32 {
+    match (metadata.is_dir(), metadata.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+/// Copies `src` to `dst` like `fsops::copy_recursive`, extracting it if it's inside an archive
+pub fn copy(src: &Path, dst: &Path) -> Result<()> {
+    match split(src) {
+        Some((archive, inner)) if !inner.as_os_str().is_empty() => extract(&archive, &inner, dst),
+        _ => fsops::copy_recursive(src, dst),
+    }
+}
+
+// Files opened from archives are extracted to a private directory, made once per run
+fn materialize_dir() -> Result<&'static Path> {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    if let Some(dir) = DIR.get() {
+        return Ok(dir);
+    }
+    let dir = fsops::private_temp_dir()?;
+
+    Ok(DIR.get_or_init(|| dir))
+}
+
+/// Returns a real path for `path`, extracting it to a temporary directory if it's inside an
+/// archive so that it can be opened by other programs
+pub fn materialize(path: &Path) -> Result<PathBuf> {
+    match split(path) {
+        Some((archive, inner)) if !inner.as_os_str().is_empty() => {
+            let archive_name = archive.file_name().unwrap_or_default();
+            let dst = materialize_dir()?.join(archive_name).join(&inner);
+
+            // Start from scratch, in case a previous extraction was edited
+            if fs::symlink_metadata(&dst).is_ok() {
+                fsops::remove_path(&dst)?;
+            }
+            extract(&archive, &inner, &dst)?;
+
+            Ok(dst)
+        }
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Packs `sources` into a new zip or tar.gz at `dst`, each at the top level of the archive.
+/// Nothing is left behind if it fails or is cancelled.
+pub fn compress(sources: &[PathBuf], dst: &Path, progress: &Progress) -> Result<()> {
+    let format = match Format::detect(dst) {
+        Some(f @ (Format::Zip | Format::TarGz)) => f,
+        _ => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Archives can only be created as .zip or .tar.gz",
+            )
+            .into())
+        }
+    };
+
+    // Everything is listed first so there's a total to show
+    let mut entries = Vec::new();
+    for source in sources {
+        let name = source
+            .file_name()
+            .expect("Trying to compress root directory");
+        list(source, Path::new(name), dst, &mut entries)?;
+    }
+    progress.start(entries.len());
+
+    let file = File::create_new(dst)?;
+    let result = if format == Format::Zip {
+        write_zip(file, &entries, progress)
+    } else {
+        write_tar_gz(file, &entries, progress)
+    };
+
+    if result.is_err() {
+        let _ = fs::remove_file(dst);
+    }
+
+    result
+}
+
+// Adds `path` and everything under it to `entries` as (path, name in the archive). Symlinks are
+// stored as links rather than followed. `dst` is left out, in case it's inside one of the sources.
+fn list(path: &Path, name: &Path, dst: &Path, entries: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    if path == dst {
+        return Ok(());
+    }
+
+    entries.push((path.to_path_buf(), name.to_path_buf()));
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        let mut children = fs::read_dir(path)?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+
+        for child in children {
+            list(&path.join(&child), &name.join(&child), dst, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_zip(file: File, entries: &[(PathBuf, PathBuf)], progress: &Progress) -> Result<()> {
+    let mut zip = ZipWriter::new(file);
+
+    for (path, name) in entries {
+        progress.advance()?;
+
+        let metadata = fs::symlink_metadata(path)?;
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(mode(&metadata));
+        let name = name.to_string_lossy();
+
+        if metadata.is_symlink() {
+            zip.add_symlink(name, fs::read_link(path)?.to_string_lossy(), options)?;
+        } else if metadata.is_dir() {
+            zip.add_directory(name, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            io::copy(&mut File::open(path)?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_tar_gz(file: File, entries: &[(PathBuf, PathBuf)], progress: &Progress) -> Result<()> {
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    tar.follow_symlinks(false);
+
+    for (path, name) in entries {
+        progress.advance()?;
+
+        // Directories are added on their own, their contents are separate entries
+        tar.append_path_with_name(path, name)?;
+    }
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tar::{EntryType, Header};
+
+    use super::*;
+    use crate::fsops::TempDir;
+
+    // The names in these archives would escape the directory they're extracted to
+    const ESCAPING: [&str; 3] = ["../escaped", "a/../../escaped", "/thunars-test-absolute"];
+
+    fn zip_with(path: &Path, build: impl FnOnce(&mut ZipWriter<File>)) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        build(&mut zip);
+        zip.finish().unwrap();
+    }
+
+    fn zip_file(zip: &mut ZipWriter<File>, name: &str, contents: &[u8]) {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+
+    // Names are written straight into the header, since `tar` refuses to build unsafe ones
+    fn tar_entry(tar: &mut tar::Builder<File>, name: &str, kind: EntryType, contents: &[u8]) {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(kind);
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append(&header, contents).unwrap();
+    }
+
+    fn tar_link(tar: &mut tar::Builder<File>, name: &str, target: &Path) {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(EntryType::Symlink);
+        header.set_link_name(target).unwrap();
+        header.set_size(0);
+        header.set_cksum();
+        tar.append(&header, &[][..]).unwrap();
+    }
+
+    // Checks that only `ok.txt`, and `link/evil` as a plain directory and file, were extracted
+    fn assert_contained(root: &Path, dst: &Path) {
+        assert_eq!(fs::read_to_string(dst.join("ok.txt")).unwrap(), "ok");
+        assert!(!root.join("escaped").exists());
+        assert!(!Path::new("/thunars-test-absolute").exists());
+        assert!(fs::read_dir(root.join("outside")).unwrap().next().is_none());
+
+        let link = fs::symlink_metadata(dst.join("link")).unwrap();
+        assert!(link.is_dir() && !link.is_symlink());
+        assert_eq!(fs::read_to_string(dst.join("link/evil")).unwrap(), "evil");
+    }
+
+    fn scratch() -> TempDir {
+        let root = TempDir::create();
+        fs::create_dir(root.join("outside")).unwrap();
+
+        root
+    }
+
+    #[test]
+    fn zip_entries_stay_inside() {
+        let root = scratch();
+        let archive = root.join("slip.zip");
+        zip_with(&archive, |zip| {
+            zip_file(zip, "ok.txt", b"ok");
+            for name in ESCAPING {
+                zip_file(zip, name, b"escaped");
+            }
+            let outside = root.join("outside").to_string_lossy().to_string();
+            zip.add_symlink("link", outside, SimpleFileOptions::default())
+                .unwrap();
+            zip_file(zip, "link/evil", b"evil");
+        });
+
+        let dst = extract_dir(&archive);
+        extract_all(&archive, &dst, &Progress::default()).unwrap();
+        assert_contained(&root, &dst);
+
+        let (dirs, files) = read_dir(&archive, Path::new("")).unwrap();
+        assert!(!dirs
+            .iter()
+            .chain(&files)
+            .any(|n| n == ".." || n == "escaped"));
+    }
+
+    #[test]
+    fn tar_entries_stay_inside() {
+        let root = scratch();
+        let archive = root.join("slip.tar");
+        let mut tar = tar::Builder::new(File::create(&archive).unwrap());
+        tar_entry(&mut tar, "ok.txt", EntryType::Regular, b"ok");
+        for name in ESCAPING {
+            tar_entry(&mut tar, name, EntryType::Regular, b"escaped");
+        }
+        tar_link(&mut tar, "link", &root.join("outside"));
+        tar_entry(&mut tar, "link/evil", EntryType::Regular, b"evil");
+        tar.finish().unwrap();
+
+        let dst = extract_dir(&archive);
+        extract_all(&archive, &dst, &Progress::default()).unwrap();
+        assert_contained(&root, &dst);
+    }
+
+    #[test]
+    fn cancelling_leaves_nothing_behind() {
+        let root = scratch();
+        fs::write(root.join("outside/a.txt"), "a").unwrap();
+
+        let cancelled = Progress::default();
+        cancelled.cancel();
+
+        for name in ["out.zip", "out.tar.gz"] {
+            let archive = root.join(name);
+            let sources = [root.join("outside")];
+            assert!(compress(&sources, &archive, &cancelled).is_err());
+            assert!(!archive.exists());
+
+            compress(&sources, &archive, &Progress::default()).unwrap();
+            let dst = extract_dir(&archive);
+            assert!(extract_all(&archive, &dst, &cancelled).is_err());
+            assert!(!dst.exists());
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compress_and_extract_round_trip() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = scratch();
+        fs::create_dir(root.join("outside/sub")).unwrap();
+        fs::write(root.join("outside/sub/run.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(
+            root.join("outside/sub/run.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        for name in ["v1.2.zip", "v1.2.tar.gz"] {
+            let archive = root.join(name);
+            let progress = Progress::default();
+            compress(&[root.join("outside")], &archive, &progress).unwrap();
+            assert_eq!(progress.done(), 3);
+            assert_eq!(progress.total(), 3);
+
+            let dst = extract_dir(&archive);
+            assert_eq!(dst, root.join("v1.2"));
+            extract_all(&archive, &dst, &Progress::default()).unwrap();
+
+            let script = dst.join("outside/sub/run.sh");
+            assert_eq!(fs::read_to_string(&script).unwrap(), "#!/bin/sh");
+            assert_eq!(
+                fs::metadata(&script).unwrap().permissions().mode() & 0o777,
+                0o755
+            );
+
+            fs::remove_dir_all(dst).unwrap();
+        }
+    }
+}